@@ -2,7 +2,8 @@ use std::path::PathBuf;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use fstools::dvdbnd::DvdBnd;
-use fstools_dvdbnd::FileKeyProvider;
+use fstools_dvdbnd::{FileKeyProvider, TocCache};
+use fstools_elden_ring_support::ELDEN_RING;
 
 pub fn vfs_open_benchmark(c: &mut Criterion) {
     c.bench_function("er_vfs_open", |b| {
@@ -10,15 +11,28 @@ pub fn vfs_open_benchmark(c: &mut Criterion) {
             let er_path = PathBuf::from(std::env::var("ER_PATH").expect("er_path"));
             let keys_path = PathBuf::from(std::env::var("ER_KEYS_PATH").expect("er_keys_path"));
             let keys = FileKeyProvider::new(keys_path);
-            let archives = [
-                er_path.join("Data0"),
-                er_path.join("Data1"),
-                er_path.join("Data2"),
-                er_path.join("Data3"),
-                er_path.join("sd/sd"),
-            ];
+            let archives = ELDEN_RING.archive_paths(&er_path);
 
-            let vfs = DvdBnd::create(archives.clone(), &keys).expect("unable to create dvdbnd");
+            let vfs = DvdBnd::create_with_keys(archives, &keys, ELDEN_RING.hash_algorithm)
+                .expect("unable to create dvdbnd");
+
+            black_box(vfs)
+        })
+    });
+}
+
+pub fn vfs_open_cached_benchmark(c: &mut Criterion) {
+    let cache = TocCache::new(std::env::temp_dir().join("fstools-bench-toc"));
+
+    c.bench_function("er_vfs_open_cached", |b| {
+        b.iter_with_large_drop(|| {
+            let er_path = PathBuf::from(std::env::var("ER_PATH").expect("er_path"));
+            let keys_path = PathBuf::from(std::env::var("ER_KEYS_PATH").expect("er_keys_path"));
+            let keys = FileKeyProvider::new(keys_path);
+            let archives = ELDEN_RING.archive_paths(&er_path);
+
+            let vfs = DvdBnd::create_with_cache(archives, &keys, ELDEN_RING.hash_algorithm, &cache)
+                .expect("unable to create dvdbnd");
 
             black_box(vfs)
         })
@@ -28,6 +42,6 @@ pub fn vfs_open_benchmark(c: &mut Criterion) {
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(20);
-    targets = vfs_open_benchmark
+    targets = vfs_open_benchmark, vfs_open_cached_benchmark
 );
 criterion_main!(benches);
@@ -5,7 +5,7 @@ use bevy::{
     asset::io::{AssetSource, AssetSourceId},
     prelude::AssetApp,
 };
-use fstools_dvdbnd::{ArchiveKeyProvider, DvdBnd};
+use fstools_dvdbnd::{ArchiveKeyProvider, DvdBnd, HashAlgorithm, LayeredDvdBnd};
 
 use crate::asset_source::{
     dvdbnd::DvdBndAssetSource,
@@ -17,17 +17,26 @@ pub(crate) mod fast_path;
 pub mod vfs;
 
 pub struct FsAssetSourcePlugin {
-    dvd_bnd: Arc<DvdBnd>,
+    dvd_bnd: Arc<LayeredDvdBnd>,
 }
 
 impl FsAssetSourcePlugin {
     pub fn new(
         data_archives: &[PathBuf],
         key_provider: impl ArchiveKeyProvider,
+        hash_algorithm: HashAlgorithm,
     ) -> io::Result<Self> {
-        let dvd_bnd = Arc::new(DvdBnd::create(data_archives, &key_provider)?);
+        let dvd_bnd = DvdBnd::create(data_archives, &key_provider, hash_algorithm)?;
 
-        Ok(Self { dvd_bnd })
+        Ok(Self::from_dvd_bnd(dvd_bnd))
+    }
+
+    /// Serve assets from an existing [`DvdBnd`], e.g. one with a dictionary attached so its
+    /// directories can be listed, or a [`LayeredDvdBnd`] with mod directories on top of it.
+    pub fn from_dvd_bnd<D: Into<LayeredDvdBnd>>(dvd_bnd: D) -> Self {
+        Self {
+            dvd_bnd: Arc::new(dvd_bnd.into()),
+        }
     }
 }
 
@@ -1,4 +1,8 @@
-use std::{io, path::Path, sync::Arc};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use bevy::{
     asset::{
@@ -7,14 +11,13 @@ use bevy::{
     },
     prelude::Deref,
 };
-use blocking::Unblock;
-use fstools_dvdbnd::{DvdBnd, DvdBndEntryError};
-use fstools_formats::dcx::DcxHeader;
+use fstools_dvdbnd::{AsyncEntryReader, DvdBndEntryError, LayeredDvdBnd};
+use futures_lite::stream;
 
 use crate::asset_source::fast_path::FastPathReader;
 
 #[derive(Clone, Deref)]
-pub struct DvdBndAssetSource(pub(crate) Arc<DvdBnd>);
+pub struct DvdBndAssetSource(pub(crate) Arc<LayeredDvdBnd>);
 
 impl AssetReader for DvdBndAssetSource {
     fn read<'a>(
@@ -23,20 +26,16 @@ impl AssetReader for DvdBndAssetSource {
     ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
         Box::pin(async move {
             let path_str = path.to_string_lossy();
-            let file = self.open(&*path_str).map_err(|err| match err {
+            let file = self.open_async(&*path_str).await.map_err(|err| match err {
                 DvdBndEntryError::NotFound => AssetReaderError::NotFound(path.to_path_buf()),
                 err => AssetReaderError::Io(Arc::new(io::Error::other(err))),
             })?;
 
-            let is_dcx = { file.data().starts_with(b"DCX\0") };
-
-            let reader = if is_dcx {
-                let (_dcx_header, dcx_reader) = DcxHeader::read(file)
-                    .map_err(|err| AssetReaderError::Io(Arc::new(io::Error::other(err))))?;
-
-                FastPathReader::Reader(Box::new(Unblock::new(dcx_reader)))
-            } else {
-                FastPathReader::MemoryMapped(file.into(), 0)
+            let reader = match AsyncEntryReader::new(file)
+                .map_err(|err| AssetReaderError::Io(Arc::new(io::Error::other(err))))?
+            {
+                AsyncEntryReader::Raw(file) => FastPathReader::MemoryMapped(file),
+                decompressed => FastPathReader::Reader(Box::new(decompressed)),
             };
 
             Ok(Box::new(reader) as Box<Reader>)
@@ -54,13 +53,37 @@ impl AssetReader for DvdBndAssetSource {
         &'a self,
         path: &'a Path,
     ) -> BoxedFuture<'a, Result<Box<PathStream>, AssetReaderError>> {
-        Box::pin(async move { Err(AssetReaderError::NotFound(path.to_path_buf())) })
+        Box::pin(async move {
+            let path_str = path.to_string_lossy();
+            if !self.is_dir(&path_str) {
+                return Err(AssetReaderError::NotFound(path.to_path_buf()));
+            }
+
+            // Asset paths are relative to the root of the source.
+            let entries: Vec<PathBuf> = self
+                .read_dir(&path_str)
+                .into_iter()
+                .map(|entry| PathBuf::from(entry.trim_start_matches('/')))
+                .collect();
+
+            Ok(Box::new(stream::iter(entries)) as Box<PathStream>)
+        })
     }
 
     fn is_directory<'a>(
         &'a self,
         path: &'a Path,
     ) -> BoxedFuture<'a, Result<bool, AssetReaderError>> {
-        Box::pin(async move { Err(AssetReaderError::NotFound(path.to_path_buf())) })
+        Box::pin(async move {
+            let path_str = path.to_string_lossy();
+
+            if self.is_dir(&path_str) {
+                Ok(true)
+            } else if self.exists(&*path_str) {
+                Ok(false)
+            } else {
+                Err(AssetReaderError::NotFound(path.to_path_buf()))
+            }
+        })
     }
 }
@@ -16,8 +16,8 @@ use bevy::{
     },
     prelude::{AssetApp, Deref, DerefMut},
 };
+use fstools_dvdbnd::DvdBndEntryReader;
 use futures_lite::{AsyncRead, AsyncReadExt};
-use memmap2::Mmap;
 use serde::{Deserialize, Serialize};
 
 pub trait FastPathAppExt: AssetApp {
@@ -113,7 +113,7 @@ impl<T: FastPathAssetLoader + Send + Sync + 'static> AssetLoader
 /// An [`AsyncRead`] implementation that allows consuming Bevy asset loaders to bypass the read
 /// implementation and directly access the data when available.
 pub enum FastPathReader<'a> {
-    MemoryMapped(Mmap, usize),
+    MemoryMapped(DvdBndEntryReader),
     Reader(Box<dyn AsyncRead + Unpin + Send + Sync + 'a>),
     Slice(&'a [u8]),
 }
@@ -122,7 +122,7 @@ impl<'a> FastPathReader<'a> {
     pub fn as_bytes(&'a self) -> Option<&'a [u8]> {
         match self {
             FastPathReader::Slice(slice) => Some(slice),
-            FastPathReader::MemoryMapped(mmap, _) => Some(&mmap[..]),
+            FastPathReader::MemoryMapped(entry) => Some(entry.data()),
             FastPathReader::Reader(_) => None,
         }
     }
@@ -137,17 +137,7 @@ impl<'a> AsyncRead for FastPathReader<'a> {
         match self.get_mut() {
             FastPathReader::Reader(reader) => AsyncRead::poll_read(pin!(reader), _cx, buf),
             FastPathReader::Slice(slice) => Poll::Ready(Read::read(slice, buf)),
-            FastPathReader::MemoryMapped(dvd_bnd, ref mut offset) => {
-                let mut data = &dvd_bnd[*offset..];
-                let read = match Read::read(&mut data, buf) {
-                    Ok(length) => length,
-                    Err(e) => return Poll::Ready(Err(e)),
-                };
-
-                *offset += read;
-
-                Poll::Ready(Ok(read))
-            }
+            FastPathReader::MemoryMapped(entry) => Poll::Ready(Read::read(entry, buf)),
         }
     }
 }
@@ -13,10 +13,9 @@ use bevy::{
     prelude::{Deref, DerefMut, Resource},
 };
 use crossbeam_channel::Sender;
-use memmap2::{Mmap, MmapOptions};
 use typed_path::Utf8WindowsPathBuf;
 
-use crate::asset_source::fast_path::FastPathReader;
+use crate::{asset_source::fast_path::FastPathReader, types::bnd4::ArchiveEntry};
 
 pub mod watcher;
 
@@ -32,7 +31,7 @@ pub enum VfsEvent {
 
 #[derive(Default)]
 pub struct VfsInner {
-    entries: HashMap<String, Mmap>,
+    entries: HashMap<String, ArchiveEntry>,
 }
 
 impl Vfs {
@@ -43,7 +42,7 @@ impl Vfs {
         }
     }
 
-    pub fn mount_file(&mut self, name: String, data: Vec<u8>) {
+    pub fn mount_file(&mut self, name: String, entry: ArchiveEntry) {
         let mut inner = self.inner.write().expect("vfs_write_lock");
 
         // TODO: this is specific to Elden Ring
@@ -59,28 +58,19 @@ impl Vfs {
             .event_sender
             .send(VfsEvent::Added(PathBuf::from(&normalized_path)));
 
-        let mut mmap = MmapOptions::default()
-            .len(data.len())
-            .map_anon()
-            .expect("failed to allocate memory");
-        mmap.copy_from_slice(&data[..]);
-
-        inner.entries.insert(
-            normalized_path,
-            mmap.make_read_only()
-                .expect("failed to make memory read-only"),
-        );
+        inner.entries.insert(normalized_path, entry);
     }
 
     pub fn entry_bytes<P: AsRef<str>>(&self, name: P) -> Option<&[u8]> {
         let inner = self.inner.read().expect("vfs_read_lock");
 
         inner.entries.get(name.as_ref()).map(|item| {
-            let ptr = item.as_ptr();
-            let len = item.len();
+            let data = item.data();
+            let ptr = data.as_ptr();
+            let len = data.len();
 
-            // SAFETY: Pointer cannot be moved and is placed on the heap for the lifetime of
-            // `self`.
+            // SAFETY: Pointer cannot be moved and is placed on the heap, kept alive by the shared
+            // buffer of the entry for the lifetime of `self`.
             unsafe { std::slice::from_raw_parts(ptr, len) }
         })
     }
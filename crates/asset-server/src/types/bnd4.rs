@@ -1,17 +1,26 @@
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::HashMap, ops::Range, sync::Arc};
 
 use bevy::{
     asset::{io::Reader, AssetLoader, BoxedFuture, Handle, LoadContext},
     prelude::{Asset, TypePath},
 };
-use fstools_formats::bnd4::BND4;
+use fstools_formats::bnd4::{Bnd4, Bnd4Error};
 use futures_lite::AsyncReadExt;
 
 pub struct Bnd4Loader;
 
-#[derive(Asset, Debug, TypePath)]
+/// A file from an [`Archive`]. Uncompressed entries share the buffer of the archive they were
+/// loaded from.
+#[derive(Asset, Clone, Debug, TypePath)]
 pub struct ArchiveEntry {
-    pub data: Vec<u8>,
+    buffer: Arc<Vec<u8>>,
+    range: Range<usize>,
+}
+
+impl ArchiveEntry {
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[self.range.clone()]
+    }
 }
 
 #[derive(Asset, Debug, Default, TypePath)]
@@ -22,7 +31,7 @@ pub struct Archive {
 impl AssetLoader for Bnd4Loader {
     type Asset = Archive;
     type Settings = ();
-    type Error = std::io::Error;
+    type Error = Bnd4Error;
 
     fn load<'a>(
         &'a self,
@@ -36,17 +45,31 @@ impl AssetLoader for Bnd4Loader {
             let mut data = Vec::new();
             reader.read_to_end(&mut data).await?;
 
-            let bnd = BND4::from_reader(Cursor::new(&data))?;
-            for file in bnd.files {
-                let handle = load_context.labeled_asset_scope(file.path.clone(), |_| {
-                    let file_offset = file.data_offset as usize;
-                    let file_end = file_offset + file.compressed_size as usize;
-                    let file_data = data[file_offset..file_end].to_vec();
+            let buffer = Arc::new(data);
+            let bnd = Bnd4::parse(&buffer)?;
+            for file in bnd.files() {
+                let file = file?;
+                let path = file.name()?.into_owned();
+
+                let entry = if file.is_compressed() {
+                    let contents = file.contents()?.into_owned();
+
+                    ArchiveEntry {
+                        range: 0..contents.len(),
+                        buffer: Arc::new(contents),
+                    }
+                } else {
+                    let start = file.data_offset as usize;
+
+                    ArchiveEntry {
+                        buffer: buffer.clone(),
+                        range: start..start + file.data().len(),
+                    }
+                };
 
-                    ArchiveEntry { data: file_data }
-                });
+                let handle = load_context.labeled_asset_scope(path.clone(), |_| entry);
 
-                archive.files.insert(file.path, handle);
+                archive.files.insert(path, handle);
             }
 
             Ok(archive)
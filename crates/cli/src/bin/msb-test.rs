@@ -1,7 +1,8 @@
-use std::{error::Error, io::Read, path::PathBuf};
+use std::{error::Error, path::PathBuf};
 
 use clap::Parser;
-use fstools_dvdbnd::{DvdBnd, FileKeyProvider};
+use fstools_dvdbnd::FileKeyProvider;
+use fstools_elden_ring_support::ELDEN_RING;
 use fstools_formats::{
     dcx::DcxHeader,
     msb::{point::PointData, Msb},
@@ -20,25 +21,16 @@ fn main() -> Result<(), Box<dyn Error>> {
     let er_path = args.erpath;
 
     let keys = FileKeyProvider::new("keys");
-    let archives = [
-        er_path.join("Data0"),
-        er_path.join("Data1"),
-        er_path.join("Data2"),
-        er_path.join("Data3"),
-        er_path.join("sd/sd"),
-    ];
-
-    let vfs = DvdBnd::create(archives.clone(), &keys).expect("unable to create vfs");
+    let vfs = ELDEN_RING
+        .load_dvd_bnd(&er_path, &keys)
+        .expect("unable to create vfs");
 
     for msb_path in MSBS.iter() {
         // println!("Parsing MSB {}", msb_path);
         let msbdcx = vfs
             .open(msb_path)
             .expect("Could not open dvdbnd entry for MSB");
-        let (_, mut decoder) = DcxHeader::read(msbdcx)?;
-
-        let mut decompressed = Vec::with_capacity(decoder.hint_size());
-        decoder.read_to_end(&mut decompressed)?;
+        let (_, decompressed) = DcxHeader::decompress_to_vec(msbdcx.data())?;
 
         let msb = Msb::parse(&decompressed).expect("Could not parse MSB");
 
@@ -0,0 +1,62 @@
+use std::{borrow::Cow, error::Error, io::Cursor};
+
+use fstools_formats::{bnd3::BND3, bnd4::Bnd4, bxf4::BXF4, dcx::DcxHeader};
+
+/// Paths and contents of the files in an archive.
+pub type BinderFiles<'a> = Vec<(String, Cow<'a, [u8]>)>;
+
+/// Read the files of a BND3 or BND4 archive, picking the binder version from its magic.
+pub fn binder_files(bytes: &[u8]) -> Result<BinderFiles<'_>, Box<dyn Error + Send + Sync>> {
+    if bytes.starts_with(b"BND3") {
+        let bnd = BND3::from_reader(Cursor::new(bytes))?;
+
+        bnd.files
+            .iter()
+            .map(|file| {
+                let contents = bnd.file_contents(file)?.into_owned();
+
+                Ok((file.path.clone(), Cow::Owned(contents)))
+            })
+            .collect()
+    } else {
+        let bnd = Bnd4::parse(bytes)?;
+
+        bnd.files()
+            .map(|file| {
+                let file = file?;
+
+                Ok((file.name()?.into_owned(), file.contents()?))
+            })
+            .collect()
+    }
+}
+
+/// Read the files of a split archive from its BHF4 header and BDF4 data files. Either half may be
+/// DCX compressed.
+pub fn split_binder_files(
+    header: &[u8],
+    data: &[u8],
+) -> Result<BinderFiles<'static>, Box<dyn Error + Send + Sync>> {
+    let header = decompress(header)?;
+    let data = decompress(data)?;
+    let bxf = BXF4::from_readers(Cursor::new(header.as_ref()), data.as_ref())?;
+
+    bxf.files
+        .iter()
+        .map(|file| {
+            let contents = bxf.file_contents(file)?.into_owned();
+
+            Ok((file.path.clone(), Cow::Owned(contents)))
+        })
+        .collect()
+}
+
+fn decompress(bytes: &[u8]) -> Result<Cow<'_, [u8]>, Box<dyn Error + Send + Sync>> {
+    if !DcxHeader::has_magic(bytes) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+
+    let (_, buffer) = DcxHeader::decompress_to_vec(bytes)?;
+
+    Ok(Cow::Owned(buffer))
+}
@@ -0,0 +1,54 @@
+use std::error::Error;
+
+use fstools_dvdbnd::LayeredDvdBnd;
+
+/// Print how many entries of each archive have a path in the dictionary or an override directory,
+/// optionally followed by the location of every entry that doesn't.
+pub fn coverage(dvd_bnd: &LayeredDvdBnd, list_unnamed: bool) -> Result<(), Box<dyn Error>> {
+    let archive_names = dvd_bnd.base().archive_names();
+    let mut totals = vec![0usize; archive_names.len()];
+    let mut unnamed = vec![0usize; archive_names.len()];
+
+    for entry in dvd_bnd.base().entries() {
+        totals[entry.archive] += 1;
+    }
+
+    let mut unnamed_entries: Vec<_> = dvd_bnd.unnamed_entries().collect();
+    for entry in &unnamed_entries {
+        unnamed[entry.archive] += 1;
+    }
+
+    println!(
+        "{:<10} {:>10} {:>10} {:>10} {:>9}",
+        "Archive", "Entries", "Named", "Unnamed", "Coverage"
+    );
+
+    for (index, name) in archive_names.iter().enumerate() {
+        let total = totals[index];
+        let named = total - unnamed[index];
+        let coverage = if total == 0 {
+            100.0
+        } else {
+            named as f64 * 100.0 / total as f64
+        };
+
+        println!(
+            "{name:<10} {total:>10} {named:>10} {:>10} {coverage:>8.2}%",
+            unnamed[index]
+        );
+    }
+
+    if list_unnamed {
+        unnamed_entries.sort_by_key(|entry| (entry.archive, entry.offset));
+
+        println!();
+        for entry in unnamed_entries {
+            println!(
+                "{:016x} {} offset={:#x} size={:#x}",
+                entry.name.0, archive_names[entry.archive], entry.offset, entry.size
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,30 +1,26 @@
-use std::{
-    error::Error,
-    io::{Cursor, Read},
-};
+use std::error::Error;
 
-use fstools_dvdbnd::DvdBnd;
-use fstools_formats::{bnd4::BND4, dcx::DcxHeader, entryfilelist::EntryFileList};
+use fstools_dvdbnd::LayeredDvdBnd;
+use fstools_formats::{dcx::DcxHeader, entryfilelist::EntryFileList};
 
-pub fn describe_bnd(dvd_bnd: &DvdBnd, name: &str) -> Result<(), Box<dyn Error>> {
-    let (dcx, mut reader) = DcxHeader::read(dvd_bnd.open(name)?)?;
+use crate::binder::binder_files;
 
-    let mut data = vec![];
-    reader.read_to_end(&mut data)?;
+pub fn describe_bnd(dvd_bnd: &LayeredDvdBnd, name: &str) -> Result<(), Box<dyn Error>> {
+    let (dcx, data) = DcxHeader::decompress_to_vec(dvd_bnd.open(name)?.data())?;
 
-    let bnd = BND4::from_reader(&mut Cursor::new(data))?;
+    let files = binder_files(&data).map_err(|e| e as Box<dyn Error>)?;
 
     println!("Compression type: {:?}", dcx.compression_parameters());
-    println!("Files: {}", bnd.files.len());
+    println!("Files: {}", files.len());
 
-    for idx in 0..bnd.files.len() {
-        println!("File[{idx}] {}", bnd.files[idx].path);
+    for (idx, (path, _)) in files.iter().enumerate() {
+        println!("File[{idx}] {path}");
     }
 
     Ok(())
 }
 
-pub fn describe_entryfilelist(dvd_bnd: &DvdBnd, name: &str) -> Result<(), Box<dyn Error>> {
+pub fn describe_entryfilelist(dvd_bnd: &LayeredDvdBnd, name: &str) -> Result<(), Box<dyn Error>> {
     let reader = dvd_bnd.open(name).expect("Could not open dvdbnd entry");
     let container = EntryFileList::from_bytes(reader.data())?;
 
@@ -47,6 +43,6 @@ pub fn describe_entryfilelist(dvd_bnd: &DvdBnd, name: &str) -> Result<(), Box<dy
     Ok(())
 }
 
-pub fn describe_matbin(_dvd_bnd: &DvdBnd, _name: &str) -> Result<(), Box<dyn Error>> {
+pub fn describe_matbin(_dvd_bnd: &LayeredDvdBnd, _name: &str) -> Result<(), Box<dyn Error>> {
     todo!()
 }
@@ -1,28 +1,27 @@
 use std::{
     error::Error,
     fs,
-    io::{Cursor, Read},
-    path::PathBuf,
+    io::Read,
+    path::{Path, PathBuf},
 };
 
-use fstools_dvdbnd::{DvdBnd, DvdBndEntryError};
-use fstools_formats::{bnd4::BND4, dcx::DcxHeader};
+use fstools_dvdbnd::{DvdBndEntryError, LayeredDvdBnd};
+use fstools_formats::{bxf4, dcx::DcxHeader};
 use indicatif::{ParallelProgressIterator, ProgressStyle};
 use rayon::prelude::*;
 
+use crate::binder::{binder_files, split_binder_files, BinderFiles};
+
 pub fn extract(
-    dvd_bnd: &DvdBnd,
+    dvd_bnd: &LayeredDvdBnd,
     recursive: bool,
     filter: Option<String>,
     output_path: PathBuf,
 ) -> Result<(), Box<dyn Error>> {
-    let lines = fstools_elden_ring_support::dictionary()
-        .filter(|line| {
-            filter
-                .as_ref()
-                .map(|filter| line.to_string_lossy().contains(filter))
-                .unwrap_or(true)
-        })
+    let lines = dvd_bnd
+        .known_entries()
+        .map(|(path, _)| path)
+        .filter(|path| filter.as_ref().is_none_or(|filter| path.contains(filter)))
         .map(std::path::PathBuf::from)
         .collect::<Vec<_>>();
 
@@ -37,37 +36,43 @@ pub fn extract(
             |total, path| {
                 match dvd_bnd.open(path.to_string_lossy().as_ref()) {
                     Ok(mut reader) => {
-                        let is_archive = recursive && path.to_string_lossy().ends_with("bnd.dcx");
+                        let path_str = path.to_string_lossy();
+                        let is_archive = recursive && path_str.ends_with("bnd.dcx");
+
+                        // Split archives are extracted through their header, which opens the
+                        // data file alongside it.
+                        let split_data_path = bxf4::data_path(&path_str).filter(|_| recursive);
+                        let is_split_data = recursive
+                            && path_str
+                                .strip_suffix("bdt")
+                                .is_some_and(|stem| dvd_bnd.open(format!("{stem}bhd")).is_ok());
+
+                        if is_split_data {
+                            return Ok(total);
+                        }
+
                         let path = path.strip_prefix("/").expect("no leading slash");
                         let parent_path = if is_archive {
                             // twice to strip "bnd.dcx"
                             output_path.join(path.with_extension("").with_extension(""))
+                        } else if split_data_path.is_some() {
+                            output_path.join(path.with_extension(""))
                         } else {
                             output_path.to_path_buf()
                         };
 
                         let _ = fs::create_dir_all(&parent_path);
 
-                        if is_archive {
-                            let (_, mut dcx_reader) = DcxHeader::read(reader)?;
-                            let mut buffer = Vec::new();
-                            dcx_reader.read_to_end(&mut buffer)?;
-
-                            let bnd4 = BND4::from_reader(Cursor::new(&buffer))?;
-
-                            for file in bnd4.files {
-                                let last_sep =
-                                    file.path.rfind('\\').map(|index| index + 1).unwrap_or(0);
-
-                                let output_path = parent_path.join(&file.path[last_sep..]);
+                        if let Some(data_path) = split_data_path {
+                            let data_reader = dvd_bnd.open(&data_path)?;
+                            let files = split_binder_files(reader.data(), data_reader.data());
 
-                                let offset = file.data_offset as usize;
-                                let size = file.compressed_size as usize;
+                            write_binder_files(files, path, &parent_path).map(|count| total + count)
+                        } else if is_archive {
+                            let (_, buffer) = DcxHeader::decompress_to_vec(reader.data())?;
 
-                                fs::write(output_path, &buffer[offset..offset + size])?;
-                            }
-
-                            Ok::<_, Box<dyn Error + Send + Sync>>(total + bnd4.file_count as usize)
+                            write_binder_files(binder_files(&buffer), path, &parent_path)
+                                .map(|count| total + count)
                         } else {
                             let mut buffer = Vec::new();
                             reader.read_to_end(&mut buffer)?;
@@ -95,3 +100,29 @@ pub fn extract(
         Err(e) => Err(e as Box<dyn Error>),
     }
 }
+
+/// Write the files of an archive to `parent_path`, returning how many were written. Malformed
+/// archives are reported and skipped so the remaining files can still be extracted.
+fn write_binder_files(
+    files: Result<BinderFiles<'_>, Box<dyn Error + Send + Sync>>,
+    path: &Path,
+    parent_path: &Path,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Skipping {}: {e}", path.display());
+            return Ok(0);
+        }
+    };
+
+    for (name, contents) in &files {
+        let last_sep = name.rfind('\\').map(|index| index + 1).unwrap_or(0);
+
+        let output_path = parent_path.join(&name[last_sep..]);
+
+        fs::write(output_path, contents)?;
+    }
+
+    Ok(files.len())
+}
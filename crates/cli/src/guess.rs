@@ -0,0 +1,55 @@
+use std::{collections::HashSet, error::Error, fs, path::PathBuf};
+
+use fstools_dvdbnd::{guess_paths, LayeredDvdBnd, PathPattern};
+
+/// Expand path patterns against the entries missing from the dictionary and print the paths that
+/// were found, one per line. Without patterns, each line of the wordlists is tried as a pattern.
+pub fn guess(
+    dvd_bnd: &LayeredDvdBnd,
+    patterns: Vec<String>,
+    wordlists: Vec<PathBuf>,
+    output_path: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut words = Vec::new();
+    for wordlist in wordlists {
+        words.extend(
+            fs::read_to_string(wordlist)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    }
+
+    let patterns = if patterns.is_empty() {
+        std::mem::take(&mut words)
+    } else {
+        patterns
+    };
+
+    let patterns = patterns
+        .iter()
+        .map(|pattern| PathPattern::parse(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let unknown: HashSet<_> = dvd_bnd.unnamed_entries().map(|entry| entry.name).collect();
+    let candidates: u64 = patterns.iter().map(|pattern| pattern.len(&words)).sum();
+
+    eprintln!(
+        "Trying {candidates} paths against {} unknown hashes",
+        unknown.len()
+    );
+
+    let matches = guess_paths(&patterns, &words, &unknown, dvd_bnd.hash_algorithm());
+    eprintln!("Found {} new paths", matches.len());
+
+    let mut dictionary = matches.join("\n");
+    dictionary.push('\n');
+
+    match output_path {
+        Some(output_path) => fs::write(output_path, dictionary)?,
+        None => print!("{dictionary}"),
+    }
+
+    Ok(())
+}
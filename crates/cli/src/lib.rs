@@ -1,16 +1,27 @@
 use std::{error::Error, path::PathBuf};
 
 use clap::{Parser, Subcommand, ValueEnum};
-use fstools_dvdbnd::{DvdBnd, FileKeyProvider};
+use directories::ProjectDirs;
+use fstools_config::Options;
+use fstools_dvdbnd::{ChainedKeyProvider, FileKeyProvider, LayeredDvdBnd, TocCache};
+use fstools_elden_ring_support::Game;
+use fstools_oodle_rt::{FindOptions, Oodle};
 
 use crate::{
+    coverage::coverage,
     describe::{describe_bnd, describe_entryfilelist, describe_matbin},
     extract::extract,
+    guess::guess,
+    verify::verify,
 };
 
+mod binder;
+mod coverage;
 mod describe;
 mod extract;
+mod guess;
 mod repl;
+mod verify;
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
@@ -19,6 +30,25 @@ pub struct Cli {
     #[arg(long, env("ER_PATH"))]
     pub game_path: PathBuf,
 
+    /// The game installed in the game path. Detected from its contents if not given.
+    #[arg(long)]
+    pub game: Option<Game>,
+
+    /// Directories with loose files that replace archive entries, like a mod folder. Later
+    /// directories take precedence.
+    #[arg(long)]
+    pub override_dir: Vec<PathBuf>,
+
+    /// Directory with the public keys of the archives, as `<archive>.pem` (e.g. `Data0.pem`).
+    /// Falls back to `paths.elden_ring_keys` in the settings file or `ER_KEYS_PATH`, as used by
+    /// the tests and benchmarks, and then to `keys`.
+    #[arg(long, env("FSTOOLS_KEYS_PATH"))]
+    pub keys_dir: Option<PathBuf>,
+
+    /// Decrypt the archive headers every time instead of caching them after the first run.
+    #[arg(long)]
+    pub no_toc_cache: bool,
+
     #[command(subcommand)]
     pub command: Action,
 }
@@ -53,11 +83,37 @@ pub enum Action {
         output_path: PathBuf,
     },
 
+    /// Print how many entries of each archive are named by the dictionary.
+    Coverage {
+        /// Also list the hash, archive, offset and size of every unnamed entry.
+        #[arg(short, long)]
+        unnamed: bool,
+    },
+
+    /// Find the paths of entries missing from the dictionary by expanding path patterns.
+    Guess {
+        /// Patterns to expand, e.g. `/chr/c{0000-9999}.{chrbnd,anibnd}.dcx`. `{word}` is replaced
+        /// by each line of the wordlists.
+        patterns: Vec<String>,
+
+        /// Files with one word per line. Without patterns, each line is used as a pattern.
+        #[arg(short, long)]
+        wordlist: Vec<PathBuf>,
+
+        /// Write the found paths to this file instead of stdout.
+        #[arg(short, long)]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Check the archive entries against the digests stored in the BHDs, to find damaged or
+    /// modified files.
+    Verify,
+
     Repl,
 }
 
 impl Action {
-    pub fn run(self, dvd_bnd: &DvdBnd) -> Result<(), Box<dyn Error>> {
+    pub fn run(self, dvd_bnd: &LayeredDvdBnd) -> Result<(), Box<dyn Error>> {
         match self {
             Action::Describe {
                 ty: AssetType::Bnd,
@@ -84,6 +140,19 @@ impl Action {
             } => {
                 extract(dvd_bnd, recursive, filter, output_path)?;
             }
+            Action::Coverage { unnamed } => {
+                coverage(dvd_bnd, unnamed)?;
+            }
+            Action::Guess {
+                patterns,
+                wordlist,
+                output_path,
+            } => {
+                guess(dvd_bnd, patterns, wordlist, output_path)?;
+            }
+            Action::Verify => {
+                verify(dvd_bnd)?;
+            }
             Action::Repl => {
                 repl::begin(dvd_bnd)?;
             }
@@ -96,18 +165,46 @@ impl Action {
 pub fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
     let Cli {
         game_path,
+        game,
+        override_dir,
+        keys_dir,
+        no_toc_cache,
         command: action,
     } = cli;
-    let keys = FileKeyProvider::new("keys");
-    let archives = [
-        game_path.join("Data0"),
-        game_path.join("Data1"),
-        game_path.join("Data2"),
-        game_path.join("Data3"),
-        game_path.join("sd/sd"),
-    ];
-
-    let dvd_bnd = DvdBnd::create(archives, &keys)?;
+    let game = game
+        .or_else(|| Game::detect(&game_path))
+        .ok_or("Could not detect the game, pass it with --game")?;
+
+    let config = Options::load().unwrap_or_default();
+    if let Some(oodle) = &config.paths.oodle {
+        Oodle::find_with(&FindOptions::new().with_path(oodle))?.make_current();
+    }
+
+    let keys_dir = keys_dir
+        .or_else(|| config.paths.elden_ring_keys.clone())
+        .unwrap_or_else(|| PathBuf::from("keys"));
+
+    let keys = ChainedKeyProvider::new().with(FileKeyProvider::new(keys_dir));
+    #[cfg(feature = "embedded-keys")]
+    let keys = keys.with(fstools_dvdbnd::EmbeddedKeyProvider::new(game.profile().id));
+
+    let cache = ProjectDirs::from("io.github", "soulsmods", "fstools")
+        .filter(|_| !no_toc_cache)
+        .map(|dirs| TocCache::new(dirs.cache_dir().join("toc")));
+
+    let base = match &cache {
+        Some(cache) => game
+            .profile()
+            .load_dvd_bnd_cached(&game_path, &keys, cache)?,
+        None => game.profile().load_dvd_bnd(&game_path, &keys)?,
+    };
+
+    let dvd_bnd = override_dir
+        .into_iter()
+        .try_fold(LayeredDvdBnd::new(base), |dvd_bnd, directory| {
+            dvd_bnd.with_override_dir(directory)
+        })?;
+
     action.run(&dvd_bnd)?;
 
     Ok(())
@@ -0,0 +1,42 @@
+use std::error::Error;
+
+use fstools_dvdbnd::{EntryIntegrity, LayeredDvdBnd};
+
+/// Check every entry of the archives against the digests stored in the BHDs and print the ones
+/// that don't match.
+pub fn verify(dvd_bnd: &LayeredDvdBnd) -> Result<(), Box<dyn Error>> {
+    let mut results = dvd_bnd.base().verify_all();
+    results.sort_by_key(|(name, _)| dvd_bnd.path(name).map(str::to_string));
+
+    let mut intact = 0;
+    let mut unverified = 0;
+    let mut failed = 0;
+
+    for (name, result) in results {
+        let path = dvd_bnd
+            .path(&name)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:016x}", name.0));
+
+        match result {
+            Ok(EntryIntegrity::Intact) => intact += 1,
+            Ok(EntryIntegrity::Unverified) => unverified += 1,
+            Ok(EntryIntegrity::Modified) => {
+                failed += 1;
+                println!("modified: {path}");
+            }
+            Err(error) => {
+                failed += 1;
+                println!("unreadable: {path} ({error})");
+            }
+        }
+    }
+
+    println!("{intact} intact, {failed} modified or unreadable, {unverified} without digest");
+
+    if failed > 0 {
+        return Err(format!("{failed} entries failed verification").into());
+    }
+
+    Ok(())
+}
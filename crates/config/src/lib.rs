@@ -1,9 +1,5 @@
 use std::{
-    env,
-    error::Error,
-    fs,
-    fs::File,
-    io,
+    env, fs, io,
     path::PathBuf,
     str::FromStr,
     sync::{Arc, RwLock},
@@ -11,15 +7,14 @@ use std::{
 
 use directories::ProjectDirs;
 use serde_derive::{Deserialize, Serialize};
-use toml::{Table, Value};
 
-use crate::paths::Paths;
+pub use crate::paths::Paths;
 
 mod paths;
 
 #[derive(Default, Deserialize, Serialize)]
-struct Options {
-    paths: Paths,
+pub struct Options {
+    pub paths: Paths,
 }
 
 thread_local! {
@@ -42,7 +37,7 @@ impl Options {
         let dirs = ProjectDirs::from("io.github", "soulsmods", "fstools")?;
         let config_dir = dirs.config_dir();
 
-        Some(config_dir.join("settings.toml"))
+        Some(config_dir.join(SETTINGS_FILENAME))
     }
 
     pub fn save(&self) -> Result<(), io::Error> {
@@ -65,6 +60,7 @@ impl Options {
 
         env_override_str(&mut options.paths.elden_ring, "ER_PATH");
         env_override_str(&mut options.paths.elden_ring_keys, "ER_KEYS_PATH");
+        env_override_str(&mut options.paths.oodle, "OODLE_PATH");
 
         Ok(options)
     }
@@ -6,4 +6,7 @@ use serde_derive::{Deserialize, Serialize};
 pub struct Paths {
     pub elden_ring: Option<PathBuf>,
     pub elden_ring_keys: Option<PathBuf>,
+
+    /// The Oodle library, or a directory containing it.
+    pub oodle: Option<PathBuf>,
 }
@@ -0,0 +1,90 @@
+use std::error::Error;
+
+#[cfg(feature = "embedded-keys")]
+fn main() -> Result<(), Box<dyn Error>> {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+    };
+
+    /// The game id, key name and path of an embedded key.
+    type Key = (String, String, PathBuf);
+
+    /// Collect the `.pem` files in the per-game subdirectories of `directory`, where the game id
+    /// is the name of the subdirectory. Keys at the top level are local to a checkout and never
+    /// embedded.
+    fn collect_keys(directory: &Path) -> Result<Vec<Key>, Box<dyn Error>> {
+        let mut keys = Vec::new();
+
+        for entry in fs::read_dir(directory)? {
+            let game_dir = entry?.path();
+            let Some(game) = game_dir.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if !game_dir.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(&game_dir)? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|extension| extension != "pem") {
+                    continue;
+                }
+
+                let name = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or_default();
+                keys.push((game.to_string(), name.to_string(), fs::canonicalize(&path)?));
+            }
+        }
+
+        keys.sort();
+
+        Ok(keys)
+    }
+
+    println!("cargo:rerun-if-env-changed=FSTOOLS_KEYS_DIR");
+
+    let keys_dir = match env::var_os("FSTOOLS_KEYS_DIR") {
+        Some(directory) => PathBuf::from(directory),
+        None => PathBuf::from(env::var("CARGO_MANIFEST_DIR")?).join("../../keys"),
+    };
+    println!("cargo:rerun-if-changed={}", keys_dir.display());
+
+    let keys = if keys_dir.is_dir() {
+        collect_keys(&keys_dir)?
+    } else {
+        Vec::new()
+    };
+
+    if keys.is_empty() {
+        return Err(format!(
+            "no keys found in {}/<game>/, add the public keys of the games as \
+             <game>/<archive>.pem or build without the embedded-keys feature",
+            keys_dir.display()
+        )
+        .into());
+    }
+
+    let mut source = String::from("pub(super) static KEYS: &[(&str, &str, &str)] = &[\n");
+    for (game, name, path) in keys {
+        println!("cargo:rerun-if-changed={}", path.display());
+        source.push_str(&format!(
+            "    ({game:?}, {name:?}, include_str!({:?})),\n",
+            path.to_string_lossy()
+        ));
+    }
+    source.push_str("];\n");
+
+    let out_path = PathBuf::from(env::var("OUT_DIR")?);
+    fs::write(out_path.join("embedded_keys.rs"), source)?;
+
+    Ok(())
+}
+
+#[cfg(not(feature = "embedded-keys"))]
+fn main() -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use blocking::{unblock, Unblock};
+use fstools_formats::dcx::{DcxContentDecoder, DcxError, DcxHeader};
+use futures_lite::{AsyncRead, AsyncSeek};
+
+use crate::{read_entry, DvdBnd, DvdBndEntryError, DvdBndEntryReader, IntoName};
+
+impl DvdBnd {
+    /// Like [`DvdBnd::open`], but entries with encrypted ranges are decrypted on the blocking
+    /// thread pool, so that the calling executor isn't held up.
+    pub async fn open_async<N: IntoName>(
+        &self,
+        name: N,
+    ) -> Result<DvdBndEntryReader, DvdBndEntryError> {
+        let entry = self
+            .entries
+            .get(&name.into_name(self.hash_algorithm))
+            .ok_or(DvdBndEntryError::NotFound)?;
+
+        let archive_file = &self.archives[entry.archive];
+        let length = entry.effective_size() as usize;
+
+        // Mapping an unencrypted entry doesn't touch its data, so it isn't worth a thread hop.
+        if entry.aes_ranges.is_empty() {
+            return read_entry(archive_file, entry, length);
+        }
+
+        let archive_file = archive_file.try_clone()?;
+        let entry = entry.clone();
+
+        unblock(move || read_entry(&archive_file, &entry, length)).await
+    }
+}
+
+/// Entries are served from memory, so reads complete immediately. Only touching pages that
+/// haven't been read from disk yet blocks, like it would for any mapped file.
+impl AsyncRead for DvdBndEntryReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Read::read(self.get_mut(), buf))
+    }
+}
+
+impl AsyncSeek for DvdBndEntryReader {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Poll::Ready(Seek::seek(self.get_mut(), pos))
+    }
+}
+
+/// An entry read asynchronously, with DCX compressed entries decompressed as they are read.
+pub enum AsyncEntryReader {
+    /// The entry isn't compressed and is read from the archive as-is.
+    Raw(DvdBndEntryReader),
+
+    /// The entry is decompressed on the blocking thread pool, streaming the output in chunks.
+    Decompressed(Unblock<DcxContentDecoder<DvdBndEntryReader>>),
+}
+
+impl AsyncEntryReader {
+    /// Wrap `reader`, decompressing its contents if it starts with a DCX header.
+    pub fn new(reader: DvdBndEntryReader) -> Result<Self, DcxError> {
+        if !reader.starts_with(b"DCX\0") {
+            return Ok(Self::Raw(reader));
+        }
+
+        let (_header, decoder) = DcxHeader::read(reader)?;
+
+        Ok(Self::Decompressed(Unblock::new(decoder)))
+    }
+}
+
+impl AsyncRead for AsyncEntryReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            AsyncEntryReader::Raw(reader) => Pin::new(reader).poll_read(cx, buf),
+            AsyncEntryReader::Decompressed(reader) => Pin::new(reader).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::SeekFrom;
+
+    use fstools_formats::dcx::{DcxAlgorithm, DcxEncoder};
+    use futures_lite::{future::block_on, AsyncReadExt, AsyncSeekExt};
+    use memmap2::MmapMut;
+
+    use super::AsyncEntryReader;
+    use crate::DvdBndEntryReader;
+
+    fn entry_reader(data: &[u8]) -> DvdBndEntryReader {
+        let mut mmap = MmapMut::map_anon(data.len()).expect("failed to map memory");
+        mmap.copy_from_slice(data);
+
+        DvdBndEntryReader::new(
+            mmap.make_read_only().expect("failed to map memory"),
+            data.len(),
+        )
+    }
+
+    #[test]
+    fn reads_asynchronously() {
+        let contents = b"0123456789abcdef".repeat(64);
+
+        block_on(async {
+            let mut reader = entry_reader(&contents);
+            reader
+                .seek(SeekFrom::Start(16))
+                .await
+                .expect("failed to seek");
+
+            let mut data = Vec::new();
+            reader
+                .read_to_end(&mut data)
+                .await
+                .expect("failed to read entry");
+            assert_eq!(data, contents[16..]);
+
+            let dcx = DcxEncoder::new(DcxAlgorithm::Deflate)
+                .encode_to_vec(&contents)
+                .expect("failed to compress");
+            let mut reader =
+                AsyncEntryReader::new(entry_reader(&dcx)).expect("failed to read DCX header");
+            assert!(matches!(reader, AsyncEntryReader::Decompressed(_)));
+
+            let mut data = Vec::new();
+            reader
+                .read_to_end(&mut data)
+                .await
+                .expect("failed to decompress entry");
+            assert_eq!(data, contents);
+        });
+    }
+}
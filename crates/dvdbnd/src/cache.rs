@@ -0,0 +1,406 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::UNIX_EPOCH,
+};
+
+use fstools_formats::bhd::{Bhd, BhdDigest, BhdFormat, BhdTocEntry};
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8; 4] = b"TOC3";
+
+/// Distinguishes the temporary files written by concurrent stores in the same process.
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory of parsed BHD tables of contents, so that the RSA decryption of an archive only has
+/// to be done the first time it is opened. The hash, sizes, offset, AES key and ranges and digest
+/// of every entry are stored, which is everything needed to read the entries from the BDT.
+///
+/// Cached files are only used while the size and modification time of the BHD they were read
+/// from are unchanged, e.g. an update of the game replaces them on the next open. Cached files
+/// that were damaged after being written are detected by their checksum and replaced as well.
+///
+/// The cache holds the AES keys of the archive entries in plaintext, so it should be kept
+/// somewhere only the current user can read. Files are created readable by their owner only on
+/// Unix.
+#[derive(Clone, Debug)]
+pub struct TocCache {
+    directory: PathBuf,
+}
+
+impl TocCache {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The table of contents of the BHD at `bhd_path` in `format`, read from the cache if it is up
+    /// to date or produced by `read` and stored otherwise. Failing to store the result isn't an
+    /// error, the cache is just skipped.
+    pub(crate) fn bhd(
+        &self,
+        bhd_path: &Path,
+        format: BhdFormat,
+        read: impl FnOnce(File) -> io::Result<Bhd>,
+    ) -> io::Result<Bhd> {
+        let bhd_file = File::open(bhd_path)?;
+        let stamp = Stamp::of(&bhd_file, format)?;
+        let cache_path = self.cache_path(bhd_path);
+
+        if let Some(toc) = read_cached(&cache_path, &stamp) {
+            return Ok(Bhd { toc });
+        }
+
+        let bhd = read(bhd_file)?;
+        let _ = write_cached(&cache_path, &stamp, &bhd.toc);
+
+        Ok(bhd)
+    }
+
+    /// Cached files are named after the archive and a hash of its full path, so that archives
+    /// with the same name from different games don't replace each other.
+    fn cache_path(&self, bhd_path: &Path) -> PathBuf {
+        let full_path = fs::canonicalize(bhd_path).unwrap_or_else(|_| bhd_path.to_path_buf());
+        let hash = Sha256::digest(full_path.as_os_str().as_encoded_bytes());
+        let hash: String = hash[..8].iter().map(|byte| format!("{byte:02x}")).collect();
+
+        let stem = bhd_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+
+        self.directory.join(format!("{stem}-{hash}.toc"))
+    }
+}
+
+/// Size and modification time of a BHD and the format it was read in, stored in front of its
+/// cached table of contents.
+#[derive(Debug, PartialEq, Eq)]
+struct Stamp {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+    format: BhdFormat,
+}
+
+impl Stamp {
+    fn of(file: &File, format: BhdFormat) -> io::Result<Self> {
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(Self {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            format,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(25);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.extend_from_slice(&self.modified_secs.to_le_bytes());
+        bytes.extend_from_slice(&self.modified_nanos.to_le_bytes());
+        bytes.push(match self.format {
+            BhdFormat::DarkSouls3 => 0,
+            BhdFormat::EldenRing => 1,
+        });
+
+        bytes
+    }
+}
+
+fn read_cached(cache_path: &Path, stamp: &Stamp) -> Option<Vec<BhdTocEntry>> {
+    let mut file = File::open(cache_path).ok()?;
+
+    let expected = stamp.to_bytes();
+    let mut header = vec![0u8; expected.len()];
+    file.read_exact(&mut header).ok()?;
+
+    if header != expected {
+        return None;
+    }
+
+    let mut checksum = [0u8; 32];
+    file.read_exact(&mut checksum).ok()?;
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).ok()?;
+
+    if Sha256::digest(&data)[..] != checksum {
+        return None;
+    }
+
+    TocReader(&data).toc()
+}
+
+fn write_cached(cache_path: &Path, stamp: &Stamp, toc: &[BhdTocEntry]) -> io::Result<()> {
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let data = toc_to_bytes(toc);
+
+    let mut contents = stamp.to_bytes();
+    contents.extend_from_slice(&Sha256::digest(&data));
+    contents.extend_from_slice(&data);
+
+    // Written next to the final path first, so that a concurrent reader never sees a partially
+    // written file.
+    let temp_path = cache_path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    create_private(&temp_path)?.write_all(&contents)?;
+    fs::rename(&temp_path, cache_path)
+}
+
+/// Serialize the entries of a table of contents, with all integers stored little-endian.
+fn toc_to_bytes(toc: &[BhdTocEntry]) -> Vec<u8> {
+    fn put_ranges(bytes: &mut Vec<u8>, ranges: &[(i64, i64)]) {
+        bytes.extend_from_slice(&(ranges.len() as u32).to_le_bytes());
+        for (start, end) in ranges {
+            bytes.extend_from_slice(&start.to_le_bytes());
+            bytes.extend_from_slice(&end.to_le_bytes());
+        }
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(toc.len() as u64).to_le_bytes());
+
+    for entry in toc {
+        bytes.extend_from_slice(&entry.hash.to_le_bytes());
+        bytes.extend_from_slice(&entry.padded_size.to_le_bytes());
+        bytes.extend_from_slice(&entry.size.to_le_bytes());
+        bytes.extend_from_slice(&entry.offset.to_le_bytes());
+        bytes.extend_from_slice(&entry.aes_key);
+        put_ranges(&mut bytes, &entry.encrypted_ranges);
+
+        match &entry.digest {
+            Some(digest) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&digest.hash);
+                put_ranges(&mut bytes, &digest.ranges);
+            }
+            None => bytes.push(0),
+        }
+    }
+
+    bytes
+}
+
+/// Reads a table of contents written by [`toc_to_bytes`], returning `None` if it's malformed.
+struct TocReader<'a>(&'a [u8]);
+
+impl TocReader<'_> {
+    fn toc(mut self) -> Option<Vec<BhdTocEntry>> {
+        let count = usize::try_from(self.u64()?).ok()?;
+
+        // The count is checked against the remaining data rather than trusted for the allocation.
+        let mut toc = Vec::with_capacity(count.min(self.0.len()));
+        for _ in 0..count {
+            toc.push(self.entry()?);
+        }
+
+        self.0.is_empty().then_some(toc)
+    }
+
+    fn entry(&mut self) -> Option<BhdTocEntry> {
+        let hash = self.u64()?;
+        let padded_size = u32::from_le_bytes(self.bytes()?);
+        let size = u32::from_le_bytes(self.bytes()?);
+        let offset = self.u64()?;
+        let aes_key = self.bytes()?;
+        let encrypted_ranges = self.ranges()?;
+
+        let digest = match self.bytes::<1>()? {
+            [0] => None,
+            [1] => Some(BhdDigest {
+                hash: self.bytes()?,
+                ranges: self.ranges()?,
+            }),
+            _ => return None,
+        };
+
+        Some(BhdTocEntry {
+            hash,
+            padded_size,
+            size,
+            offset,
+            aes_key,
+            encrypted_ranges,
+            digest,
+        })
+    }
+
+    fn ranges(&mut self) -> Option<Vec<(i64, i64)>> {
+        let count = u32::from_le_bytes(self.bytes()?);
+
+        (0..count)
+            .map(|_| {
+                let start = i64::from_le_bytes(self.bytes()?);
+                let end = i64::from_le_bytes(self.bytes()?);
+
+                Some((start, end))
+            })
+            .collect()
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+
+        Some(*bytes)
+    }
+}
+
+/// Create a file only its owner can read, as it contains the archive keys.
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::unix::fs::OpenOptionsExt};
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<File> {
+    File::create(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, fs};
+
+    use fstools_formats::bhd::{Bhd, BhdDigest, BhdFormat, BhdTocEntry};
+
+    use super::TocCache;
+    use crate::test::TempDir;
+
+    fn toc() -> Vec<BhdTocEntry> {
+        vec![
+            BhdTocEntry {
+                hash: 0x1234_5678_9abc_def0,
+                padded_size: 0x40,
+                size: 0x30,
+                offset: 0x1000,
+                aes_key: [0x1; 16],
+                encrypted_ranges: vec![(0, 0x10), (-1, -1)],
+                digest: Some(BhdDigest {
+                    hash: [0x2; 32],
+                    ranges: vec![(0x10, 0x30)],
+                }),
+            },
+            BhdTocEntry {
+                hash: 0x42,
+                padded_size: 0x10,
+                size: 0,
+                offset: 0x2000,
+                aes_key: [0; 16],
+                encrypted_ranges: Vec::new(),
+                digest: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn reuses_until_modified() {
+        let root = TempDir::new("toc-cache");
+
+        let bhd_path = root.join("Data0.bhd");
+        fs::write(&bhd_path, b"encrypted").expect("failed to write BHD");
+
+        let cache = TocCache::new(root.join("cache"));
+        let decryptions = Cell::new(0);
+        let decrypt = |_| {
+            decryptions.set(decryptions.get() + 1);
+            Ok(Bhd { toc: toc() })
+        };
+
+        for _ in 0..2 {
+            let bhd = cache
+                .bhd(&bhd_path, BhdFormat::EldenRing, decrypt)
+                .expect("failed to read BHD");
+            assert_eq!(bhd.toc, toc());
+        }
+        assert_eq!(decryptions.get(), 1);
+
+        cache
+            .bhd(&bhd_path, BhdFormat::DarkSouls3, decrypt)
+            .expect("failed to read BHD");
+        assert_eq!(decryptions.get(), 2);
+
+        fs::write(&bhd_path, b"encrypted, but longer").expect("failed to write BHD");
+        cache
+            .bhd(&bhd_path, BhdFormat::DarkSouls3, decrypt)
+            .expect("failed to read BHD");
+        assert_eq!(decryptions.get(), 3);
+    }
+
+    #[test]
+    fn replaces_damaged_files() {
+        let root = TempDir::new("toc-damaged");
+
+        let bhd_path = root.join("Data0.bhd");
+        fs::write(&bhd_path, b"encrypted").expect("failed to write BHD");
+
+        let cache = TocCache::new(root.join("cache"));
+        let decryptions = Cell::new(0);
+        let decrypt = |_| {
+            decryptions.set(decryptions.get() + 1);
+            Ok(Bhd { toc: toc() })
+        };
+
+        cache
+            .bhd(&bhd_path, BhdFormat::EldenRing, decrypt)
+            .expect("failed to read BHD");
+
+        let cache_path = cache.cache_path(&bhd_path);
+        let file_name = cache_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("invalid cache file name");
+        assert_eq!(file_name.len(), "Data0-0123456789abcdef.toc".len());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = fs::metadata(&cache_path).expect("failed to stat cached TOC");
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+
+        let mut contents = fs::read(&cache_path).expect("failed to read cached TOC");
+        let last = contents.len() - 1;
+        contents[last] ^= 0xFF;
+        fs::write(&cache_path, contents).expect("failed to damage cached TOC");
+
+        for _ in 0..2 {
+            let bhd = cache
+                .bhd(&bhd_path, BhdFormat::EldenRing, decrypt)
+                .expect("failed to read BHD");
+            assert_eq!(bhd.toc, toc());
+        }
+        assert_eq!(decryptions.get(), 2);
+    }
+}
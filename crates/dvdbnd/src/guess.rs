@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{HashAlgorithm, Name};
+
+#[derive(Debug, Error)]
+pub enum PatternError {
+    #[error("Unclosed template in pattern at {0}")]
+    UnclosedTemplate(usize),
+
+    #[error("Invalid template {{{0}}}")]
+    InvalidTemplate(String),
+}
+
+/// A path template that expands to every combination of its parts, used to find the paths behind
+/// [`Name`] hashes that aren't in the dictionary.
+///
+/// Templates are written between braces:
+/// - `{0000-9999}` expands to each number in the range, zero-padded to the width of the start.
+/// - `{a,b,c}` expands to each of the alternatives.
+/// - `{word}` expands to each word of the wordlist passed when expanding.
+///
+/// For example `/chr/c{0000-9999}.{chrbnd,anibnd}.dcx` expands to 20000 paths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathPattern {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Range { start: u64, end: u64, width: usize },
+    Choice(Vec<String>),
+    Word,
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let mut parts = Vec::new();
+        let mut rest = pattern;
+
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+
+            let close = rest[open..]
+                .find('}')
+                .ok_or(PatternError::UnclosedTemplate(
+                    pattern.len() - rest.len() + open,
+                ))?;
+
+            parts.push(Part::parse(&rest[open + 1..open + close])?);
+            rest = &rest[open + close + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Number of paths this pattern expands to with `words` as the wordlist.
+    pub fn len(&self, words: &[String]) -> u64 {
+        self.parts
+            .iter()
+            .fold(1u64, |len, part| len.saturating_mul(part.len(words)))
+    }
+
+    pub fn is_empty(&self, words: &[String]) -> bool {
+        self.len(words) == 0
+    }
+
+    /// The path at `index` out of [`PathPattern::len`] combinations.
+    pub fn path(&self, mut index: u64, words: &[String]) -> String {
+        let mut choices = vec![0; self.parts.len()];
+
+        // The last part varies fastest so paths come out in lexicographic order of their parts.
+        for (choice, part) in choices.iter_mut().zip(&self.parts).rev() {
+            let len = part.len(words);
+            *choice = index % len;
+            index /= len;
+        }
+
+        let mut path = String::new();
+        for (choice, part) in choices.into_iter().zip(&self.parts) {
+            match part {
+                Part::Literal(literal) => path.push_str(literal),
+                Part::Range { start, width, .. } => {
+                    path.push_str(&format!("{:0width$}", start + choice, width = *width));
+                }
+                Part::Choice(alternatives) => path.push_str(&alternatives[choice as usize]),
+                Part::Word => path.push_str(&words[choice as usize]),
+            }
+        }
+
+        path
+    }
+
+    /// All paths this pattern expands to, generated in parallel.
+    pub fn paths<'a>(&'a self, words: &'a [String]) -> impl ParallelIterator<Item = String> + 'a {
+        (0..self.len(words))
+            .into_par_iter()
+            .map(move |index| self.path(index, words))
+    }
+}
+
+impl Part {
+    fn parse(template: &str) -> Result<Self, PatternError> {
+        if template == "word" {
+            return Ok(Part::Word);
+        }
+
+        if template.contains(',') {
+            return Ok(Part::Choice(
+                template.split(',').map(str::to_string).collect(),
+            ));
+        }
+
+        let invalid = || PatternError::InvalidTemplate(template.to_string());
+        let (start, end) = template.split_once('-').ok_or_else(invalid)?;
+        let width = start.len();
+        let start: u64 = start.parse().map_err(|_| invalid())?;
+        let end: u64 = end.parse().map_err(|_| invalid())?;
+
+        if start > end {
+            return Err(invalid());
+        }
+
+        Ok(Part::Range { start, end, width })
+    }
+
+    fn len(&self, words: &[String]) -> u64 {
+        match self {
+            Part::Literal(_) => 1,
+            Part::Range { start, end, .. } => (end - start).saturating_add(1),
+            Part::Choice(alternatives) => alternatives.len() as u64,
+            Part::Word => words.len() as u64,
+        }
+    }
+}
+
+/// Expand every pattern and return the paths whose hash is in `unknown`, sorted and in the form
+/// they're hashed in, ready to be appended to a dictionary.
+pub fn guess_paths(
+    patterns: &[PathPattern],
+    words: &[String],
+    unknown: &HashSet<Name>,
+    hash_algorithm: HashAlgorithm,
+) -> Vec<String> {
+    let mut matches: Vec<String> = patterns
+        .par_iter()
+        .flat_map(|pattern| pattern.paths(words))
+        .filter(|path| unknown.contains(&hash_algorithm.hash(path)))
+        .map(crate::normalize_path)
+        .collect();
+
+    matches.sort();
+    matches.dedup();
+
+    matches
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{guess_paths, PathPattern};
+    use crate::{HashAlgorithm, Name};
+
+    #[test]
+    fn expands_templates() {
+        let words = vec!["a".to_string(), "b".to_string()];
+        let pattern =
+            PathPattern::parse("/chr/c{0998-1000}_{word}.{chrbnd,anibnd}.dcx").expect("valid");
+
+        assert_eq!(pattern.len(&words), 12);
+        assert_eq!(pattern.path(0, &words), "/chr/c0998_a.chrbnd.dcx");
+        assert_eq!(pattern.path(11, &words), "/chr/c1000_b.anibnd.dcx");
+
+        let unknown = HashSet::from([Name::from("/chr/c0999_b.chrbnd.dcx")]);
+        assert_eq!(
+            guess_paths(&[pattern], &words, &unknown, HashAlgorithm::Hash64),
+            ["/chr/c0999_b.chrbnd.dcx"]
+        );
+
+        assert!(PathPattern::parse("/chr/c{0000").is_err());
+        assert!(PathPattern::parse("/chr/c{9-0}").is_err());
+    }
+}
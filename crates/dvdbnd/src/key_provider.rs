@@ -1,11 +1,31 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
 
 use fstools_formats::bhd::BhdKey;
 
 pub trait ArchiveKeyProvider {
-    fn get_key(&self, name: &str) -> Result<BhdKey, std::io::Error>;
+    /// The public key the BHD of the archive `name` (e.g. `Data0`) is encrypted with. Providers
+    /// that don't have the key return an error of kind [`ErrorKind::NotFound`].
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error>;
 }
 
+impl<K: ArchiveKeyProvider + ?Sized> ArchiveKeyProvider for &K {
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error> {
+        (**self).get_key(name)
+    }
+}
+
+impl<K: ArchiveKeyProvider + ?Sized> ArchiveKeyProvider for Box<K> {
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error> {
+        (**self).get_key(name)
+    }
+}
+
+/// Reads keys from `<name>.pem` files in a directory.
 pub struct FileKeyProvider {
     key_dir: PathBuf,
 }
@@ -19,8 +39,200 @@ impl FileKeyProvider {
 }
 
 impl ArchiveKeyProvider for FileKeyProvider {
-    fn get_key(&self, name: &str) -> Result<BhdKey, std::io::Error> {
-        fs::read_to_string(self.key_dir.join(name).with_extension("pem"))
-            .and_then(|pem| BhdKey::from_pem(&pem).map_err(std::io::Error::other))
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error> {
+        let path = self.key_dir.join(name).with_extension("pem");
+        let pem = fs::read_to_string(&path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Could not read key {name} from {}: {err}", path.display()),
+            )
+        })?;
+
+        parse_key(name, &pem)
+    }
+}
+
+/// Keys held in memory as PEM strings, e.g. read from a config file or bundled by an application.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryKeyProvider {
+    keys: HashMap<String, String>,
+}
+
+impl MemoryKeyProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key<N: Into<String>, P: Into<String>>(mut self, name: N, pem: P) -> Self {
+        self.insert(name, pem);
+        self
+    }
+
+    pub fn insert<N: Into<String>, P: Into<String>>(&mut self, name: N, pem: P) {
+        self.keys.insert(name.into(), pem.into());
+    }
+}
+
+impl<N: Into<String>, P: Into<String>> FromIterator<(N, P)> for MemoryKeyProvider {
+    fn from_iter<T: IntoIterator<Item = (N, P)>>(iter: T) -> Self {
+        Self {
+            keys: iter
+                .into_iter()
+                .map(|(name, pem)| (name.into(), pem.into()))
+                .collect(),
+        }
+    }
+}
+
+impl ArchiveKeyProvider for MemoryKeyProvider {
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error> {
+        let pem = self
+            .keys
+            .get(name)
+            .ok_or_else(|| missing_key(name, "the in-memory keys"))?;
+
+        parse_key(name, pem)
+    }
+}
+
+/// Asks each of a list of providers in turn, returning the first key found. A provider that has
+/// the key but fails to load it stops the search, so that a broken key isn't silently replaced.
+#[derive(Default)]
+pub struct ChainedKeyProvider {
+    providers: Vec<Box<dyn ArchiveKeyProvider + Send + Sync>>,
+}
+
+impl ChainedKeyProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a provider that is asked after the ones added before it.
+    pub fn with<K: ArchiveKeyProvider + Send + Sync + 'static>(mut self, provider: K) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+}
+
+impl ArchiveKeyProvider for ChainedKeyProvider {
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error> {
+        let mut reasons = Vec::new();
+
+        for provider in &self.providers {
+            match provider.get_key(name) {
+                Err(err) if err.kind() == ErrorKind::NotFound => reasons.push(err.to_string()),
+                result => return result,
+            }
+        }
+
+        Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("Key {name} not found: {}", reasons.join("; ")),
+        ))
+    }
+}
+
+/// Keys compiled into the binary with the `embedded-keys` feature, read from the per-game
+/// subdirectories of the directory named by the `FSTOOLS_KEYS_DIR` environment variable at build
+/// time (`keys/` in the workspace by default). A key like `keys/ds3/Data1.pem` is only used for
+/// the game with that id.
+///
+/// The build fails if no per-game keys are found. Keys at the top level of the directory are
+/// ignored by git and never embedded, they can be read with a [`FileKeyProvider`] instead.
+#[cfg(feature = "embedded-keys")]
+#[derive(Clone, Debug)]
+pub struct EmbeddedKeyProvider {
+    game: String,
+}
+
+#[cfg(feature = "embedded-keys")]
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/embedded_keys.rs"));
+}
+
+#[cfg(feature = "embedded-keys")]
+impl EmbeddedKeyProvider {
+    /// Provide the keys for the game with id `game`, e.g. `er` or `ds3`.
+    pub fn new<S: Into<String>>(game: S) -> Self {
+        Self { game: game.into() }
+    }
+
+    /// Names of the keys embedded for this game.
+    pub fn key_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        embedded::KEYS
+            .iter()
+            .filter(|(game, _, _)| *game == self.game)
+            .map(|(_, name, _)| *name)
+    }
+}
+
+#[cfg(feature = "embedded-keys")]
+impl ArchiveKeyProvider for EmbeddedKeyProvider {
+    fn get_key(&self, name: &str) -> Result<BhdKey, io::Error> {
+        let pem = embedded::KEYS
+            .iter()
+            .find(|(game, key_name, _)| *game == self.game && *key_name == name)
+            .map(|(_, _, pem)| *pem)
+            .ok_or_else(|| missing_key(name, &format!("the keys embedded for {}", self.game)))?;
+
+        parse_key(name, pem)
+    }
+}
+
+fn missing_key(name: &str, source: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::NotFound,
+        format!("Key {name} is not in {source}"),
+    )
+}
+
+fn parse_key(name: &str, pem: &str) -> Result<BhdKey, io::Error> {
+    BhdKey::from_pem(pem).map_err(|err| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Key {name} is not a valid PKCS#1 RSA public key: {err}"),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use super::{ArchiveKeyProvider, ChainedKeyProvider, FileKeyProvider, MemoryKeyProvider};
+
+    /// A throwaway key generated for this test.
+    const TEST_KEY: &str = "\
+-----BEGIN RSA PUBLIC KEY-----
+MIGJAoGBAMdGefCkuS4Ngeidzy8Gmdf51k2RVRApFNNkbz24Dv7fDs7bphHrzKCx
+40ZuHSXLBn8NUFgIUEI0nuLcmh7yJ8iZ5SEeuuX5l/Q6vLoGAjmV84jB7xK1YSxs
+BP82vGfGiHYUHyegSY7GA1JjZkV5eHHWrhVAtHUUE9xFVIJMOlCxAgMBAAE=
+-----END RSA PUBLIC KEY-----
+";
+
+    #[test]
+    fn chains_providers() {
+        let keys = ChainedKeyProvider::new()
+            .with(FileKeyProvider::new("/nonexistent"))
+            .with(
+                MemoryKeyProvider::new()
+                    .with_key("Data0", "not a key")
+                    .with_key("Data1", TEST_KEY),
+            );
+
+        assert!(keys.get_key("Data1").is_ok());
+
+        let Err(err) = keys.get_key("Data0") else {
+            panic!("invalid key was accepted");
+        };
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("Data0"));
+
+        let Err(err) = keys.get_key("sd") else {
+            panic!("missing key was found");
+        };
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().contains("Key sd not found"));
+        assert!(err.to_string().contains("in-memory keys"));
     }
 }
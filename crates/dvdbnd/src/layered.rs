@@ -0,0 +1,343 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use memmap2::{Mmap, MmapMut};
+
+use crate::{
+    normalize_path, read_dir_paths, DvdBnd, DvdBndEntryError, DvdBndEntryInfo, DvdBndEntryReader,
+    HashAlgorithm, IntoName, Name,
+};
+
+/// A [`DvdBnd`] with other sources of files stacked on top of it, the way the game and mod loaders
+/// prefer loose files over archive entries.
+///
+/// Layers added later take precedence over earlier ones, and all of them over the base archives.
+/// For example, with a mod directory added after a DLC archive, a file in the mod directory
+/// replaces the same file in the DLC, which in turn replaces the one in the base archives.
+pub struct LayeredDvdBnd {
+    base: DvdBnd,
+    layers: Vec<Layer>,
+}
+
+enum Layer {
+    Directory(OverrideDirectory),
+    Archive(DvdBnd),
+}
+
+/// Files on disk, indexed by the hash of their path relative to the root of the directory.
+struct OverrideDirectory {
+    paths: BTreeMap<String, PathBuf>,
+
+    /// Paths in [`OverrideDirectory::paths`] by their hash.
+    names: HashMap<Name, String>,
+}
+
+impl LayeredDvdBnd {
+    pub fn new(base: DvdBnd) -> Self {
+        Self {
+            base,
+            layers: Vec::new(),
+        }
+    }
+
+    /// Stack the files in `directory` on top of the current layers. `directory` is laid out like
+    /// the archives, e.g. `mod/chr/c0000.chrbnd.dcx` overrides `/chr/c0000.chrbnd.dcx`.
+    ///
+    /// The contents of the directory are indexed once here, so files added later are not picked
+    /// up.
+    pub fn with_override_dir<P: AsRef<Path>>(mut self, directory: P) -> io::Result<Self> {
+        let directory = OverrideDirectory::scan(directory.as_ref(), self.hash_algorithm())?;
+        self.layers.push(Layer::Directory(directory));
+
+        Ok(self)
+    }
+
+    /// Stack the entries of another set of archives on top of the current layers, e.g. the
+    /// archives of a DLC.
+    pub fn with_archive(mut self, dvd_bnd: DvdBnd) -> Self {
+        self.layers.push(Layer::Archive(dvd_bnd));
+        self
+    }
+
+    /// The archives at the bottom of the stack.
+    pub fn base(&self) -> &DvdBnd {
+        &self.base
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.base.hash_algorithm()
+    }
+
+    /// Open a reader to the file identified by `name` in the topmost layer that has it.
+    pub fn open<N: IntoName>(&self, name: N) -> Result<DvdBndEntryReader, DvdBndEntryError> {
+        let name = name.into_name(self.hash_algorithm());
+
+        for layer in self.layers.iter().rev() {
+            let result = match layer {
+                Layer::Directory(directory) => directory.open(&name),
+                Layer::Archive(dvd_bnd) => dvd_bnd.open(&name),
+            };
+
+            match result {
+                Err(DvdBndEntryError::NotFound) => continue,
+                result => return result,
+            }
+        }
+
+        self.base.open(name)
+    }
+
+    /// Like [`LayeredDvdBnd::open`], using [`DvdBnd::open_async`] for archive layers.
+    #[cfg(feature = "async")]
+    pub async fn open_async<N: IntoName>(
+        &self,
+        name: N,
+    ) -> Result<DvdBndEntryReader, DvdBndEntryError> {
+        let name = name.into_name(self.hash_algorithm());
+
+        for layer in self.layers.iter().rev() {
+            let result = match layer {
+                Layer::Directory(directory) => directory.open(&name),
+                Layer::Archive(dvd_bnd) => dvd_bnd.open_async(&name).await,
+            };
+
+            match result {
+                Err(DvdBndEntryError::NotFound) => continue,
+                result => return result,
+            }
+        }
+
+        self.base.open_async(name).await
+    }
+
+    /// Whether any layer has an entry for `name`.
+    pub fn exists<N: IntoName>(&self, name: N) -> bool {
+        let name = name.into_name(self.hash_algorithm());
+
+        self.base.exists(&name)
+            || self.layers.iter().any(|layer| match layer {
+                Layer::Directory(directory) => directory.names.contains_key(&name),
+                Layer::Archive(dvd_bnd) => dvd_bnd.exists(&name),
+            })
+    }
+
+    /// The path of an entry, if any layer knows it. Files in override directories always have a
+    /// known path, which can name entries that are missing from the dictionary of the archives.
+    pub fn path(&self, name: &Name) -> Option<&str> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| match layer {
+                Layer::Directory(overrides) => overrides.names.get(name).map(String::as_str),
+                Layer::Archive(dvd_bnd) => dvd_bnd.path(name),
+            })
+            .or_else(|| self.base.path(name))
+    }
+
+    /// All entries with a known path in any of the layers, ordered by path. Paths present in
+    /// several layers are listed once.
+    pub fn known_entries(&self) -> impl Iterator<Item = (&str, &Name)> {
+        let mut entries: BTreeMap<&str, &Name> = self.base.known_entries().collect();
+
+        for layer in &self.layers {
+            match layer {
+                Layer::Directory(overrides) => entries.extend(
+                    overrides
+                        .names
+                        .iter()
+                        .map(|(name, path)| (path.as_str(), name)),
+                ),
+                Layer::Archive(dvd_bnd) => entries.extend(dvd_bnd.known_entries()),
+            }
+        }
+
+        entries.into_iter()
+    }
+
+    /// Entries of the base archives whose path isn't known to any layer, see
+    /// [`DvdBnd::unnamed_entries`].
+    pub fn unnamed_entries(&self) -> impl Iterator<Item = DvdBndEntryInfo> + '_ {
+        self.base
+            .unnamed_entries()
+            .filter(|entry| self.path(&entry.name).is_none())
+    }
+
+    /// The files and directories directly inside `directory` in any of the layers, as full
+    /// paths. See [`DvdBnd::read_dir`].
+    pub fn read_dir(&self, directory: &str) -> BTreeSet<&str> {
+        let mut entries: BTreeSet<&str> = self.base.read_dir(directory).collect();
+
+        for layer in &self.layers {
+            match layer {
+                Layer::Directory(overrides) => {
+                    entries.extend(read_dir_paths(&overrides.paths, directory));
+                }
+                Layer::Archive(dvd_bnd) => entries.extend(dvd_bnd.read_dir(directory)),
+            }
+        }
+
+        entries
+    }
+
+    /// Whether any known entry of any layer is stored below `directory`.
+    pub fn is_dir(&self, directory: &str) -> bool {
+        self.base.is_dir(directory)
+            || self.layers.iter().any(|layer| match layer {
+                Layer::Directory(overrides) => {
+                    read_dir_paths(&overrides.paths, directory).next().is_some()
+                }
+                Layer::Archive(dvd_bnd) => dvd_bnd.is_dir(directory),
+            })
+    }
+}
+
+impl From<DvdBnd> for LayeredDvdBnd {
+    fn from(base: DvdBnd) -> Self {
+        Self::new(base)
+    }
+}
+
+impl OverrideDirectory {
+    fn scan(root: &Path, hash_algorithm: HashAlgorithm) -> io::Result<Self> {
+        let mut files = Vec::new();
+        collect_files(root, &mut files)?;
+
+        let mut paths = BTreeMap::new();
+        let mut names = HashMap::new();
+
+        for file in files {
+            let relative = file.strip_prefix(root).map_err(io::Error::other)?;
+            let path = normalize_path(relative);
+
+            names.insert(hash_algorithm.hash(&path), path.clone());
+            paths.insert(path, file);
+        }
+
+        Ok(Self { paths, names })
+    }
+
+    fn open(&self, name: &Name) -> Result<DvdBndEntryReader, DvdBndEntryError> {
+        let path = self
+            .names
+            .get(name)
+            .and_then(|path| self.paths.get(path))
+            .ok_or(DvdBndEntryError::NotFound)?;
+        let file = File::open(path)?;
+        let length = file.metadata()?.len() as usize;
+
+        // Empty files can't be mapped.
+        let mmap = if length == 0 {
+            MmapMut::map_anon(1)?.make_read_only()?
+        } else {
+            // SAFETY: no safety guarantees here. File could be modified while we read from it.
+            unsafe { Mmap::map(&file)? }
+        };
+
+        Ok(DvdBndEntryReader::new(mmap, length))
+    }
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Read};
+
+    use super::LayeredDvdBnd;
+    use crate::{test::TempDir, DvdBnd, DvdBndEntryError, Name, VfsFileEntry};
+
+    #[test]
+    fn overrides_take_precedence() {
+        let root = TempDir::new("layered");
+
+        for (layer, contents) in [("first", &b"first"[..]), ("second", &b"second"[..])] {
+            fs::create_dir_all(root.join(layer).join("chr")).expect("failed to create layer");
+            fs::write(root.join(layer).join("chr/c0000.anibnd.dcx"), contents)
+                .expect("failed to write file");
+        }
+        fs::write(root.join("first/empty.txt"), b"").expect("failed to write file");
+
+        let base = DvdBnd::from_entries(Vec::new(), []);
+
+        let layered = LayeredDvdBnd::new(base)
+            .with_override_dir(root.join("first"))
+            .and_then(|layered| layered.with_override_dir(root.join("second")))
+            .expect("failed to scan overrides");
+
+        let mut contents = String::new();
+        layered
+            .open(Name::from("/chr/c0000.anibnd.dcx"))
+            .expect("failed to open override")
+            .read_to_string(&mut contents)
+            .expect("failed to read override");
+        assert_eq!(contents, "second");
+
+        assert_eq!(
+            layered.open("empty.txt").expect("failed to open").data(),
+            b""
+        );
+        assert!(matches!(
+            layered.open("/chr/c0001.anibnd.dcx"),
+            Err(DvdBndEntryError::NotFound)
+        ));
+        assert!(layered.exists("CHR\\c0000.anibnd.dcx"));
+        assert!(layered.is_dir("/chr"));
+        assert_eq!(
+            layered.read_dir("/").into_iter().collect::<Vec<_>>(),
+            ["/chr", "/empty.txt"]
+        );
+    }
+
+    #[test]
+    fn lists_entries_of_all_layers() {
+        let root = TempDir::new("known");
+
+        fs::create_dir_all(root.join("chr")).expect("failed to create layer");
+        fs::write(root.join("chr/c0000.anibnd.dcx"), b"override").expect("failed to write file");
+        fs::write(root.join("chr/c0001.anibnd.dcx"), b"added").expect("failed to write file");
+
+        let entries = ["/chr/c0000.anibnd.dcx", "/chr/c0000.chrbnd.dcx"]
+            .into_iter()
+            .map(|path| (Name::from(path), VfsFileEntry::unencrypted(0)));
+
+        let base =
+            DvdBnd::from_entries(Vec::new(), entries).with_dictionary(["/chr/c0000.chrbnd.dcx"]);
+        assert_eq!(base.unnamed_entries().count(), 1);
+
+        let layered = LayeredDvdBnd::new(base)
+            .with_override_dir(root.path())
+            .expect("failed to scan overrides");
+
+        let known: Vec<_> = layered.known_entries().map(|(path, _)| path).collect();
+        assert_eq!(
+            known,
+            [
+                "/chr/c0000.anibnd.dcx",
+                "/chr/c0000.chrbnd.dcx",
+                "/chr/c0001.anibnd.dcx"
+            ]
+        );
+        assert_eq!(
+            layered.path(&Name::from("/chr/c0000.anibnd.dcx")),
+            Some("/chr/c0000.anibnd.dcx")
+        );
+        assert_eq!(layered.unnamed_entries().count(), 0);
+    }
+}
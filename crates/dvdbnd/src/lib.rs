@@ -1,21 +1,39 @@
-use std::{collections::HashMap, fs::File, io::Error, ops::Range, path::Path, slice};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Error,
+    ops::Range,
+    path::Path,
+};
 
 use aes::{
-    cipher::{consts::U16, generic_array::GenericArray, BlockDecrypt, BlockSizeUser, KeyInit},
+    cipher::{generic_array::GenericArray, BlockDecrypt, BlockSizeUser, KeyInit},
     Aes128,
 };
-use fstools_formats::bhd::Bhd;
+use fstools_formats::bhd::{Bhd, BhdDigest, BhdTocEntry};
 use memmap2::MmapOptions;
-use rayon::{iter::ParallelBridge, prelude::ParallelIterator};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+pub use self::async_io::AsyncEntryReader;
+#[cfg(feature = "embedded-keys")]
+pub use self::key_provider::EmbeddedKeyProvider;
 pub use self::{
-    key_provider::{ArchiveKeyProvider, FileKeyProvider},
-    name::Name,
+    cache::TocCache,
+    guess::{guess_paths, PathPattern, PatternError},
+    key_provider::{ArchiveKeyProvider, ChainedKeyProvider, FileKeyProvider, MemoryKeyProvider},
+    layered::LayeredDvdBnd,
+    name::{normalize_path, HashAlgorithm, IntoName, Name},
     reader::DvdBndEntryReader,
 };
 
+#[cfg(feature = "async")]
+mod async_io;
+mod cache;
+mod guess;
 mod key_provider;
+mod layered;
 mod name;
 mod reader;
 
@@ -34,150 +52,630 @@ pub enum DvdBndEntryError {
 /// A read-only virtual filesystem layered over the BHD/BDT archives of a FROMSOFTWARE game.
 pub struct DvdBnd {
     archives: Vec<File>,
+    hash_algorithm: HashAlgorithm,
+
+    /// File names of the archives without extension, e.g. `Data0` or `sd`.
+    archive_names: Vec<String>,
     entries: HashMap<Name, VfsFileEntry>,
+
+    /// Known paths of the entries, sorted so that the contents of a directory are adjacent.
+    paths: BTreeMap<String, Name>,
+
+    /// Reverse index of [`DvdBnd::paths`].
+    names: HashMap<Name, String>,
 }
 
 impl DvdBnd {
     fn load_archive<P: AsRef<Path>>(
         path: P,
+        key_name: &str,
         key_provider: &impl ArchiveKeyProvider,
+        hash_algorithm: HashAlgorithm,
+        cache: Option<&TocCache>,
     ) -> Result<(File, Bhd), Error> {
         let path = path.as_ref();
-        let bhd_file = File::open(path.with_extension("bhd"))?;
+        let bhd_path = path.with_extension("bhd");
         let bdt_file = File::open(path.with_extension("bdt"))?;
-        let name = path
-            .file_stem()
-            .and_then(|stem| stem.to_str())
-            .ok_or(Error::other("invalid archive path given"))?;
 
-        let key = key_provider.get_key(name)?;
-        let bhd = Bhd::read(bhd_file, key)?;
+        let format = hash_algorithm.bhd_format();
+        let read = |bhd_file: File| {
+            let decrypted = Bhd::decrypt(bhd_file, &key_provider.get_key(key_name)?)?;
+
+            Bhd::from_decrypted(&decrypted, format)
+        };
+
+        let bhd = match cache {
+            Some(cache) => cache.bhd(&bhd_path, format, read)?,
+            None => read(File::open(bhd_path)?)?,
+        };
 
         Ok((bdt_file, bhd))
     }
 
     /// Create a virtual filesystem from the archive files (BHD or BDT) pointed to by
-    /// [`archive_paths`].
+    /// [`archive_paths`]. Entry paths are hashed with `hash_algorithm`, which has to match the
+    /// game the archives are from.
+    ///
+    /// The key of each archive is looked up by its file name, e.g. `Data0` for `Data0.bhd`.
     pub fn create<P: AsRef<Path>, K: ArchiveKeyProvider>(
         archive_paths: impl IntoIterator<Item = P>,
         key_provider: &K,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self, Error> {
+        let archives = archive_paths
+            .into_iter()
+            .map(|path| {
+                let key_name = path
+                    .as_ref()
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or(Error::other("invalid archive path given"))?
+                    .to_string();
+
+                Ok((path, key_name))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Self::create_with_keys(archives, key_provider, hash_algorithm)
+    }
+
+    /// Like [`DvdBnd::create`], but with the name of the key of each archive given alongside its
+    /// path.
+    pub fn create_with_keys<P: AsRef<Path>, S: AsRef<str>, K: ArchiveKeyProvider>(
+        archives: impl IntoIterator<Item = (P, S)>,
+        key_provider: &K,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self, Error> {
+        Self::create_inner(archives, key_provider, hash_algorithm, None)
+    }
+
+    /// Like [`DvdBnd::create_with_keys`], but the BHD entries are stored in `cache` and read from
+    /// there on later calls, instead of being decrypted again. Keys are only requested for
+    /// archives that aren't cached yet.
+    pub fn create_with_cache<P: AsRef<Path>, S: AsRef<str>, K: ArchiveKeyProvider>(
+        archives: impl IntoIterator<Item = (P, S)>,
+        key_provider: &K,
+        hash_algorithm: HashAlgorithm,
+        cache: &TocCache,
+    ) -> Result<Self, Error> {
+        Self::create_inner(archives, key_provider, hash_algorithm, Some(cache))
+    }
+
+    fn create_inner<P: AsRef<Path>, S: AsRef<str>, K: ArchiveKeyProvider>(
+        archives: impl IntoIterator<Item = (P, S)>,
+        key_provider: &K,
+        hash_algorithm: HashAlgorithm,
+        cache: Option<&TocCache>,
     ) -> Result<Self, Error> {
-        let mut archives = Vec::new();
+        let mut archive_files = Vec::new();
+        let mut archive_names = Vec::new();
         let mut entries = HashMap::new();
 
-        archive_paths
+        archives
             .into_iter()
             .enumerate()
-            .try_for_each(|(index, path)| {
+            .try_for_each(|(index, (path, key_name))| {
                 let path = path.as_ref();
-                let (mmap, bhd) = Self::load_archive(path, key_provider)?;
-
-                archives.push(mmap);
-                entries.extend(bhd.toc.into_iter().map(|entry| {
-                    (
-                        Name(entry.hash),
-                        VfsFileEntry {
-                            archive: index,
-                            file_size: entry.size,
-                            file_size_with_padding: entry.padded_size,
-                            file_offset: entry.offset,
-                            aes_key: entry.aes_key,
-                            aes_ranges: entry
-                                .encrypted_ranges
-                                .into_iter()
-                                .filter_map(|range| match range {
-                                    (-1, -1) => None,
-                                    (start, end) if start == end => None,
-                                    (start, end) => Some(start as u64..end as u64),
-                                })
-                                .collect(),
-                        },
-                    )
-                }));
+                let (mmap, bhd) = Self::load_archive(
+                    path,
+                    key_name.as_ref(),
+                    key_provider,
+                    hash_algorithm,
+                    cache,
+                )?;
+
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                archive_files.push(mmap);
+                archive_names.push(name);
+                entries.extend(
+                    bhd.toc
+                        .into_iter()
+                        .map(|entry| (Name(entry.hash), VfsFileEntry::new(index, entry))),
+                );
 
                 Ok::<_, Error>(())
             })?;
 
-        Ok(DvdBnd { archives, entries })
+        Ok(DvdBnd {
+            archives: archive_files,
+            hash_algorithm,
+            archive_names,
+            entries,
+            paths: BTreeMap::new(),
+            names: HashMap::new(),
+        })
     }
 
-    /// Open a reader to the file identified by [name].
-    pub fn open<N: Into<Name>>(&self, name: N) -> Result<DvdBndEntryReader, DvdBndEntryError> {
-        match self.entries.get(&name.into()) {
-            Some(entry) => {
-                let archive_file = &self.archives[entry.archive];
-                let offset = entry.file_offset as usize;
-                let encrypted_size = entry.file_size_with_padding as usize;
-
-                // SAFETY: no safety guarantees here. File could be modified while we read from it.
-                let mut mmap = unsafe {
-                    MmapOptions::new()
-                        .offset(offset as u64)
-                        .len(encrypted_size)
-                        .map_copy(archive_file)?
-                };
-
-                let data_ptr = mmap.as_mut_ptr();
-                let data_cipher = Aes128::new(&GenericArray::from(entry.aes_key));
-                let encrypted_blocks: Result<Vec<&mut [GenericArray<u8, U16>]>, _> = entry
-                    .aes_ranges
-                    .iter()
-                    .map(|range| {
-                        let size = (range.end - range.start) as usize;
-
-                        if range.start >= mmap.len() as u64 || range.end > mmap.len() as u64 {
-                            return Err(DvdBndEntryError::CorruptEntry);
-                        }
-
-                        let num_blocks = size / Aes128::block_size();
-
-                        // SAFETY: We check the offset added to `data_ptr` is within the bounds of a
-                        // valid pointer.
-                        let blocks: &mut [GenericArray<u8, U16>] = unsafe {
-                            slice::from_raw_parts_mut(
-                                data_ptr.add(range.start as usize).cast(),
-                                num_blocks,
-                            )
-                        };
-
-                        Ok(blocks)
-                    })
-                    .collect();
-
-                encrypted_blocks?
-                    .into_iter()
-                    .par_bridge()
-                    .for_each(|blocks| {
-                        data_cipher.decrypt_blocks(blocks);
-                    });
-
-                #[cfg(unix)]
-                let _ = mmap.advise(memmap2::Advice::Sequential);
-
-                // DCXes dont have an unpadded size set
-                let effective_file_size = if entry.file_size != 0 {
-                    entry.file_size
-                } else {
-                    entry.file_size_with_padding
-                } as usize;
-
-                Ok(DvdBndEntryReader::new(
-                    mmap.make_read_only()?,
-                    effective_file_size,
-                ))
+    /// Attach a dictionary of paths, e.g. `fstools_elden_ring_support::dictionary()`, to make the
+    /// entries it names available to [`DvdBnd::list`] and [`DvdBnd::read_dir`]. Paths that
+    /// aren't in any of the archives are ignored.
+    pub fn with_dictionary<P: AsRef<Path>>(
+        mut self,
+        dictionary: impl IntoIterator<Item = P>,
+    ) -> Self {
+        for path in dictionary {
+            let path = normalize_path(path);
+            let name = self.hash_algorithm.hash(&path);
+
+            if self.entries.contains_key(&name) {
+                self.names.insert(name.clone(), path.clone());
+                self.paths.insert(path, name);
             }
+        }
+
+        self
+    }
+
+    /// Whether the archives contain an entry for `name`, regardless of whether its path is known.
+    pub fn exists<N: IntoName>(&self, name: N) -> bool {
+        self.entries
+            .contains_key(&name.into_name(self.hash_algorithm))
+    }
+
+    /// The algorithm entry paths are hashed with.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// The path of an entry, if it is in the dictionary.
+    pub fn path(&self, name: &Name) -> Option<&str> {
+        self.names.get(name).map(String::as_str)
+    }
+
+    /// All entries with a known path, ordered by path.
+    pub fn known_entries(&self) -> impl Iterator<Item = (&str, &Name)> {
+        self.paths.iter().map(|(path, name)| (path.as_str(), name))
+    }
+
+    /// Paths of all known entries starting with `prefix`, e.g. `/chr/c0000` or `/map/m10/`.
+    pub fn list<'a>(&'a self, prefix: &str) -> impl Iterator<Item = &'a str> + 'a {
+        list_paths(&self.paths, prefix)
+    }
+
+    /// The files and directories directly inside `directory`, as full paths. Only entries with a
+    /// known path are listed.
+    pub fn read_dir<'a>(&'a self, directory: &str) -> impl Iterator<Item = &'a str> + 'a {
+        read_dir_paths(&self.paths, directory)
+    }
+
+    /// Whether any known entry is stored below `directory`.
+    pub fn is_dir(&self, directory: &str) -> bool {
+        self.read_dir(directory).next().is_some()
+    }
+
+    /// File names of the archives this filesystem was created from, in the order they were given.
+    /// Indexes into this match [`DvdBndEntryInfo::archive`].
+    pub fn archive_names(&self) -> &[String] {
+        &self.archive_names
+    }
+
+    /// Location of every entry in the archives, in no particular order.
+    pub fn entries(&self) -> impl Iterator<Item = DvdBndEntryInfo> + '_ {
+        self.entries.iter().map(|(name, entry)| DvdBndEntryInfo {
+            name: name.clone(),
+            archive: entry.archive,
+            offset: entry.file_offset,
+            size: entry.effective_size(),
+        })
+    }
+
+    /// Entries whose path isn't in the dictionary attached with [`DvdBnd::with_dictionary`].
+    pub fn unnamed_entries(&self) -> impl Iterator<Item = DvdBndEntryInfo> + '_ {
+        self.entries()
+            .filter(|entry| !self.names.contains_key(&entry.name))
+    }
+
+    /// Open a reader to the file identified by [name].
+    pub fn open<N: IntoName>(&self, name: N) -> Result<DvdBndEntryReader, DvdBndEntryError> {
+        match self.entries.get(&name.into_name(self.hash_algorithm)) {
+            Some(entry) => read_entry(
+                &self.archives[entry.archive],
+                entry,
+                entry.effective_size() as usize,
+            ),
             None => Err(DvdBndEntryError::NotFound),
         }
     }
+
+    /// Check the entry identified by `name` against the digest stored for it in the BHD.
+    pub fn verify<N: IntoName>(&self, name: N) -> Result<EntryIntegrity, DvdBndEntryError> {
+        let entry = self
+            .entries
+            .get(&name.into_name(self.hash_algorithm))
+            .ok_or(DvdBndEntryError::NotFound)?;
+
+        self.verify_entry(entry)
+    }
+
+    /// Check every entry against its digest in parallel, see [`DvdBnd::verify`]. Results are in
+    /// no particular order.
+    pub fn verify_all(&self) -> Vec<(Name, Result<EntryIntegrity, DvdBndEntryError>)> {
+        self.entries
+            .par_iter()
+            .map(|(name, entry)| (name.clone(), self.verify_entry(entry)))
+            .collect()
+    }
+
+    fn verify_entry(&self, entry: &VfsFileEntry) -> Result<EntryIntegrity, DvdBndEntryError> {
+        let Some(digest) = &entry.digest else {
+            return Ok(EntryIntegrity::Unverified);
+        };
+
+        let reader = read_entry(
+            &self.archives[entry.archive],
+            entry,
+            entry.file_size_with_padding as usize,
+        )?;
+
+        match digest.matches(reader.data()) {
+            Some(true) => Ok(EntryIntegrity::Intact),
+            Some(false) => Ok(EntryIntegrity::Modified),
+            None => Err(DvdBndEntryError::CorruptEntry),
+        }
+    }
+}
+
+/// Map the first `length` bytes of `entry` from `archive_file`. Encrypted ranges are decrypted
+/// into overlays, the rest of the entry is read from the shared mapping.
+fn read_entry(
+    archive_file: &File,
+    entry: &VfsFileEntry,
+    length: usize,
+) -> Result<DvdBndEntryReader, DvdBndEntryError> {
+    let offset = entry.file_offset;
+    let padded_size = entry.file_size_with_padding as usize;
+
+    // SAFETY: no safety guarantees here. File could be modified while we read from it.
+    let mmap = unsafe {
+        MmapOptions::new()
+            .offset(offset)
+            .len(padded_size)
+            .map(archive_file)?
+    };
+
+    #[cfg(unix)]
+    let _ = mmap.advise(memmap2::Advice::Sequential);
+
+    if entry.aes_ranges.is_empty() {
+        return Ok(DvdBndEntryReader::new(mmap, length));
+    }
+
+    let data_cipher = Aes128::new(&GenericArray::from(entry.aes_key));
+    let overlays = entry
+        .aes_ranges
+        .par_iter()
+        .map(|range| {
+            // Ranges come straight from the BHD, which might have them reversed or out of bounds.
+            let start = usize::try_from(range.start).map_err(|_| DvdBndEntryError::CorruptEntry)?;
+            let end = usize::try_from(range.end).map_err(|_| DvdBndEntryError::CorruptEntry)?;
+            let mut data = mmap
+                .get(start..end)
+                .ok_or(DvdBndEntryError::CorruptEntry)?
+                .to_vec();
+
+            for block in data.chunks_exact_mut(Aes128::block_size()) {
+                data_cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            }
+
+            Ok((start, data))
+        })
+        .collect::<Result<Vec<_>, DvdBndEntryError>>()?;
+
+    Ok(DvdBndEntryReader::with_overlays(mmap, length, overlays))
 }
 
-#[derive(Debug)]
+/// Paths in `paths` starting with `prefix`, which is normalized first.
+pub(crate) fn list_paths<'a, V>(
+    paths: &'a BTreeMap<String, V>,
+    prefix: &str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let prefix = normalize_path(prefix);
+
+    paths
+        .range(prefix.clone()..)
+        .map(|(path, _)| path.as_str())
+        .take_while(move |path| path.starts_with(&prefix))
+}
+
+/// The files and directories in `paths` directly inside `directory`.
+pub(crate) fn read_dir_paths<'a, V>(
+    paths: &'a BTreeMap<String, V>,
+    directory: &str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let mut prefix = normalize_path(directory);
+    if !prefix.ends_with('/') {
+        prefix.push('/');
+    }
+
+    let prefix_len = prefix.len();
+    let mut previous: Option<&'a str> = None;
+
+    list_paths(paths, &prefix).filter_map(move |path| {
+        // Entries in subdirectories are reported once, as the path of the subdirectory. Paths
+        // are sorted, so all entries of a subdirectory are adjacent.
+        let child = match path[prefix_len..].find('/') {
+            Some(end) => &path[..prefix_len + end],
+            None => path,
+        };
+
+        if previous == Some(child) {
+            return None;
+        }
+
+        previous = Some(child);
+        Some(child)
+    })
+}
+
+/// Where an entry is stored in the archives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DvdBndEntryInfo {
+    pub name: Name,
+
+    /// Index of the archive the entry is stored in, see [`DvdBnd::archive_names`].
+    pub archive: usize,
+
+    /// Offset of the entry data in the BDT file of the archive.
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Result of checking an entry against its digest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryIntegrity {
+    /// The entry matches its digest.
+    Intact,
+
+    /// The entry doesn't match its digest, so it was damaged or modified.
+    Modified,
+
+    /// The BHD has no digest for the entry.
+    Unverified,
+}
+
+#[derive(Clone, Debug)]
 pub struct VfsFileEntry {
     archive: usize,
-    #[allow(unused)]
     file_size: u32,
     file_size_with_padding: u32,
     file_offset: u64,
     aes_key: [u8; 16],
     aes_ranges: Vec<Range<u64>>,
+    digest: Option<BhdDigest>,
+}
+
+impl VfsFileEntry {
+    fn new(archive: usize, entry: BhdTocEntry) -> Self {
+        Self {
+            archive,
+            file_size: entry.size,
+            file_size_with_padding: entry.padded_size,
+            file_offset: entry.offset,
+            aes_key: entry.aes_key,
+            aes_ranges: entry
+                .encrypted_ranges
+                .into_iter()
+                .filter_map(|range| match range {
+                    (-1, -1) => None,
+                    (start, end) if start == end => None,
+                    (start, end) => Some(start as u64..end as u64),
+                })
+                .collect(),
+            digest: entry.digest,
+        }
+    }
+
+    fn effective_size(&self) -> u64 {
+        // DCXes dont have an unpadded size set
+        if self.file_size != 0 {
+            self.file_size
+        } else {
+            self.file_size_with_padding
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        fs::{self, File},
+        ops::Range,
+        path::{Path, PathBuf},
+    };
+
+    use fstools_formats::bhd::{Bhd, BhdBuilder, BhdFormat};
+
+    use super::{
+        read_entry, DvdBnd, DvdBndEntryError, EntryIntegrity, HashAlgorithm, Name, VfsFileEntry,
+    };
+
+    impl DvdBnd {
+        /// An archive set serving `entries` from the BDT files `archives`, without a dictionary.
+        /// Archives are named `Data0`, `Data1`, etc. for every archive the entries refer to.
+        pub(crate) fn from_entries(
+            archives: Vec<File>,
+            entries: impl IntoIterator<Item = (Name, VfsFileEntry)>,
+        ) -> Self {
+            let entries: HashMap<_, _> = entries.into_iter().collect();
+            let archive_count = entries
+                .values()
+                .map(|entry| entry.archive + 1)
+                .fold(archives.len(), usize::max);
+
+            Self {
+                archives,
+                hash_algorithm: HashAlgorithm::Hash64,
+                archive_names: (0..archive_count)
+                    .map(|index| format!("Data{index}"))
+                    .collect(),
+                entries,
+                paths: BTreeMap::new(),
+                names: HashMap::new(),
+            }
+        }
+    }
+
+    impl VfsFileEntry {
+        /// An unencrypted entry of `size` bytes at the start of the first archive.
+        pub(crate) fn unencrypted(size: u32) -> Self {
+            Self {
+                archive: 0,
+                file_size: size,
+                file_size_with_padding: size,
+                file_offset: 0,
+                aes_key: [0; 16],
+                aes_ranges: Vec::new(),
+                digest: None,
+            }
+        }
+    }
+
+    /// A directory in the system temp directory, created empty and removed again when dropped.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// Create `fstools-<name>-<pid>`, `name` has to be unique across the tests of the crate.
+        pub(crate) fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("fstools-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).expect("failed to create temp directory");
+
+            Self(path)
+        }
+
+        pub(crate) fn path(&self) -> &Path {
+            &self.0
+        }
+
+        pub(crate) fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+            self.0.join(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn lists_known_entries() {
+        let paths = [
+            "/chr/c0000.anibnd.dcx",
+            "/chr/c0000.chrbnd.dcx",
+            "/map/m10/m10_00_00_00.tpfbhd",
+            "/map/m10/m10_00_00_00.tpfbdt",
+        ];
+
+        let entries = paths
+            .iter()
+            .map(|path| (Name::from(path), VfsFileEntry::unencrypted(0)));
+
+        let dvd_bnd = DvdBnd::from_entries(Vec::new(), entries)
+            .with_dictionary([
+                "CHR\\c0000.chrbnd.dcx",
+                "/chr/c0000.anibnd.dcx",
+                "/chr/c9999.chrbnd.dcx",
+            ])
+            .with_dictionary(&paths[2..3]);
+
+        assert_eq!(dvd_bnd.known_entries().count(), 3);
+        assert_eq!(
+            dvd_bnd.path(&Name::from("/chr/c0000.chrbnd.dcx")),
+            Some("/chr/c0000.chrbnd.dcx")
+        );
+        assert_eq!(dvd_bnd.list("/chr/c0000.c").collect::<Vec<_>>(), [paths[1]]);
+        assert_eq!(dvd_bnd.read_dir("/").collect::<Vec<_>>(), ["/chr", "/map"]);
+        assert_eq!(dvd_bnd.read_dir("chr").count(), 2);
+        assert!(dvd_bnd.is_dir("/map/m10"));
+        assert!(!dvd_bnd.is_dir("/map/m10/m10_00_00_00.tpfbhd"));
+        assert!(dvd_bnd.exists("/chr/c0000.chrbnd.dcx"));
+        assert!(!dvd_bnd.exists("/chr/c9999.chrbnd.dcx"));
+
+        let unnamed: Vec<_> = dvd_bnd.unnamed_entries().collect();
+        assert_eq!(unnamed.len(), 1);
+        assert_eq!(unnamed[0].name, Name::from(paths[3]));
+        assert_eq!(dvd_bnd.archive_names()[unnamed[0].archive], "Data0");
+    }
+
+    #[test]
+    fn verifies_entries() {
+        let mut builder = BhdBuilder::new(BhdFormat::EldenRing);
+        builder
+            .add_file("/regulation.bin", vec![0x1u8; 21])
+            .add_encrypted_file(
+                "/chr/c0000.anibnd.dcx",
+                vec![0x2u8; 48],
+                [0x3; 16],
+                vec![0..16, 32..48],
+            );
+
+        let mut bhd = Vec::new();
+        let mut bdt = Vec::new();
+        builder
+            .write(&mut bhd, &mut bdt)
+            .expect("failed to write archive");
+
+        let bhd = Bhd::from_decrypted(&bhd, BhdFormat::EldenRing).expect("failed to read BHD");
+        let regulation_offset = bhd
+            .toc
+            .iter()
+            .find(|entry| entry.hash == builder.files[0].hash)
+            .map(|entry| entry.offset as usize)
+            .expect("entry not found");
+
+        let root = TempDir::new("verify");
+        let bdt_path = root.join("Data0.bdt");
+        fs::write(&bdt_path, &bdt).expect("failed to write BDT");
+
+        let dvd_bnd = DvdBnd::from_entries(
+            vec![File::open(&bdt_path).expect("failed to open BDT")],
+            bhd.toc
+                .into_iter()
+                .map(|entry| (Name(entry.hash), VfsFileEntry::new(0, entry))),
+        );
+
+        assert!(dvd_bnd
+            .verify_all()
+            .into_iter()
+            .all(|(_, result)| matches!(result, Ok(EntryIntegrity::Intact))));
+
+        bdt[regulation_offset] ^= 0xff;
+        fs::write(&bdt_path, &bdt).expect("failed to write BDT");
+
+        assert!(matches!(
+            dvd_bnd.verify("/regulation.bin"),
+            Ok(EntryIntegrity::Modified)
+        ));
+        assert!(matches!(
+            dvd_bnd.verify("/chr/c0000.anibnd.dcx"),
+            Ok(EntryIntegrity::Intact)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_aes_ranges() {
+        let root = TempDir::new("aes-ranges");
+        let archive_path = root.join("Data0.bdt");
+        fs::write(&archive_path, [0x0u8; 64]).expect("failed to write BDT");
+        let archive = File::open(&archive_path).expect("failed to open BDT");
+
+        // Reversed and out of bounds
+        let ranges = [Range { start: 32, end: 16 }, 48..80];
+        for range in ranges {
+            let entry = VfsFileEntry {
+                aes_ranges: vec![range],
+                ..VfsFileEntry::unencrypted(64)
+            };
+
+            let result = read_entry(&archive, &entry, 64);
+            assert!(matches!(result, Err(DvdBndEntryError::CorruptEntry)));
+        }
+    }
 }
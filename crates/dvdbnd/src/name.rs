@@ -1,43 +1,103 @@
 use std::path::Path;
 
+use fstools_formats::bhd::{self, BhdFormat};
+
+/// The hash of an entry path, which is how entries are identified in the archives.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Name(pub u64);
 
+/// Hashes a path with [`HashAlgorithm::Hash64`], the algorithm used by Elden Ring. Use
+/// [`HashAlgorithm::hash`] for archives of other games.
 impl<S: AsRef<Path>> From<S> for Name {
     fn from(value: S) -> Self {
-        let value = value.as_ref();
-        let prefix = if !value.starts_with("/") {
-            Some('/')
-        } else {
-            None
-        };
-
-        let hash = prefix
-            .into_iter()
-            .chain(
-                value
-                    .to_string_lossy()
-                    .chars()
-                    .map(|ch| ch.to_ascii_lowercase()),
-            )
-            .map(|ch| match ch {
-                '\\' => '/',
-                _ => ch,
-            })
-            .fold(0u64, |hash, next| {
-                hash.wrapping_mul(0x85).wrapping_add(next as u64)
-            });
-
-        Name(hash)
+        HashAlgorithm::Hash64.hash(value)
+    }
+}
+
+/// The algorithm a game uses to hash entry paths into [`Name`]s. Both hash the normalized path,
+/// see [`normalize_path`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    /// 32-bit hash with a multiplier of `0x25`, used by Dark Souls III and Sekiro.
+    Hash32,
+
+    /// 64-bit hash with a multiplier of `0x85`, used by Elden Ring and Armored Core VI.
+    #[default]
+    Hash64,
+}
+
+impl HashAlgorithm {
+    pub fn hash<S: AsRef<Path>>(self, path: S) -> Name {
+        let path = path.as_ref().to_string_lossy();
+
+        Name(self.bhd_format().hash_path(&path))
+    }
+
+    /// The TOC layout of the BHD files of games using this algorithm.
+    pub fn bhd_format(self) -> BhdFormat {
+        match self {
+            HashAlgorithm::Hash32 => BhdFormat::DarkSouls3,
+            HashAlgorithm::Hash64 => BhdFormat::EldenRing,
+        }
+    }
+}
+
+/// Anything that identifies an entry: either a [`Name`] or a path that still has to be hashed.
+pub trait IntoName {
+    fn into_name(self, algorithm: HashAlgorithm) -> Name;
+}
+
+impl IntoName for Name {
+    fn into_name(self, _algorithm: HashAlgorithm) -> Name {
+        self
     }
 }
 
+impl IntoName for &Name {
+    fn into_name(self, _algorithm: HashAlgorithm) -> Name {
+        self.clone()
+    }
+}
+
+impl<S: AsRef<Path>> IntoName for S {
+    fn into_name(self, algorithm: HashAlgorithm) -> Name {
+        algorithm.hash(self)
+    }
+}
+
+/// The form a path is hashed in: lowercase, using `/` as separator and with a leading `/`.
+pub fn normalize_path<S: AsRef<Path>>(path: S) -> String {
+    bhd::normalize_path(&path.as_ref().to_string_lossy())
+}
+
 #[cfg(test)]
 mod test {
-    use super::Name;
+    use super::{normalize_path, HashAlgorithm, Name};
 
     #[test]
     pub fn adds_prefix() {
         assert_eq!(Name::from("/path"), Name::from("path"));
     }
+
+    #[test]
+    fn normalizes_separators_and_case() {
+        assert_eq!(
+            normalize_path("Chr\\C0000.chrbnd.dcx"),
+            "/chr/c0000.chrbnd.dcx"
+        );
+        assert_eq!(
+            Name::from("Chr\\C0000.chrbnd.dcx"),
+            Name::from("/chr/c0000.chrbnd.dcx")
+        );
+    }
+
+    #[test]
+    fn hashes_32_bit() {
+        // '/' * 0x25 + 'a'
+        assert_eq!(HashAlgorithm::Hash32.hash("A"), Name(47 * 0x25 + 97));
+        assert_ne!(
+            HashAlgorithm::Hash32.hash("/chr/c0000.chrbnd.dcx"),
+            HashAlgorithm::Hash64.hash("/chr/c0000.chrbnd.dcx")
+        );
+    }
 }
@@ -1,38 +1,100 @@
-use std::io::{Error, Read, Seek, SeekFrom};
+use std::{
+    io::{Error, Read, Seek, SeekFrom},
+    sync::OnceLock,
+};
 
 use memmap2::Mmap;
 
+/// A reader over an entry, served from a read-only mapping of the archive shared with other
+/// readers. Ranges of the entry that are encrypted in the archive are decrypted into separate
+/// overlay buffers instead of a private copy of the whole entry.
 pub struct DvdBndEntryReader {
     mmap: Mmap,
+
+    /// Decrypted encrypted ranges of the entry as `(offset, data)`, sorted by offset.
+    overlays: Vec<(usize, Vec<u8>)>,
+
+    /// The entry with the overlays applied, only created when [`DvdBndEntryReader::data`] is
+    /// called on an entry with overlays.
+    contiguous: OnceLock<Vec<u8>>,
     position: usize,
     length: usize,
 }
 
 impl DvdBndEntryReader {
     pub fn new(mmap: Mmap, length: usize) -> Self {
+        Self::with_overlays(mmap, length, Vec::new())
+    }
+
+    /// Create a reader that returns the contents of `overlays` in place of the mapped data they
+    /// cover. Overlays must lie within `mmap` and must not overlap.
+    pub(crate) fn with_overlays(
+        mmap: Mmap,
+        length: usize,
+        mut overlays: Vec<(usize, Vec<u8>)>,
+    ) -> Self {
+        overlays.sort_by_key(|(offset, _)| *offset);
+
         Self {
             mmap,
+            overlays,
+            contiguous: OnceLock::new(),
             position: 0,
             length,
         }
     }
 
+    /// The contents of the entry. For entries with encrypted ranges this copies the entry the
+    /// first time it is called, prefer [`Read`] for large entries.
     pub fn data(&self) -> &[u8] {
-        &self.mmap[..self.length]
+        if self.overlays.is_empty() {
+            return &self.mmap[..self.length];
+        }
+
+        self.contiguous.get_or_init(|| {
+            let mut data = self.mmap[..self.length].to_vec();
+
+            for (offset, overlay) in &self.overlays {
+                let end = (offset + overlay.len()).min(self.length);
+                if *offset < end {
+                    data[*offset..end].copy_from_slice(&overlay[..end - offset]);
+                }
+            }
+
+            data
+        })
     }
-}
 
-// Do we really need this? With the length being a thing now to deal with the
-// padding on the output this conversion is no longer lossless.
-impl From<DvdBndEntryReader> for Mmap {
-    fn from(value: DvdBndEntryReader) -> Self {
-        value.mmap
+    /// Whether the entry starts with `prefix`, e.g. the magic of a format. Unlike checking
+    /// [`DvdBndEntryReader::data`], this never copies the entry.
+    pub fn starts_with(&self, prefix: &[u8]) -> bool {
+        self.chunk_at(0).starts_with(prefix)
+    }
+
+    /// The contiguous bytes starting at `position`, from either an overlay or the mapping.
+    fn chunk_at(&self, position: usize) -> &[u8] {
+        if position >= self.length {
+            return &[];
+        }
+
+        let next_overlay = self
+            .overlays
+            .iter()
+            .find(|(offset, overlay)| offset + overlay.len() > position);
+
+        let chunk = match next_overlay {
+            Some((offset, overlay)) if *offset <= position => &overlay[position - offset..],
+            Some((offset, _)) => &self.mmap[position..*offset],
+            None => &self.mmap[position..],
+        };
+
+        &chunk[..chunk.len().min(self.length - position)]
     }
 }
 
 impl Read for DvdBndEntryReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let mut data = &self.data()[self.position..];
+        let mut data = self.chunk_at(self.position);
         let read = data.read(buf)?;
 
         self.position += read;
@@ -45,12 +107,12 @@ impl Seek for DvdBndEntryReader {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         let new_pos = match pos {
             SeekFrom::Start(start_offset) => Some(start_offset as usize),
-            SeekFrom::End(end_offset) => self.data().len().checked_add_signed(end_offset as isize),
+            SeekFrom::End(end_offset) => self.length.checked_add_signed(end_offset as isize),
             SeekFrom::Current(offset) => self.position.checked_add_signed(offset as isize),
         }
         .ok_or(Error::other("invalid seek offset"))?;
 
-        if new_pos < self.data().len() {
+        if new_pos < self.length {
             self.position = new_pos;
             Ok(self.position as u64)
         } else {
@@ -58,3 +120,36 @@ impl Seek for DvdBndEntryReader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Seek, SeekFrom};
+
+    use memmap2::MmapMut;
+
+    use super::DvdBndEntryReader;
+
+    #[test]
+    fn reads_through_overlays() {
+        let mut mmap = MmapMut::map_anon(48).expect("failed to map memory");
+        mmap.fill(b'.');
+
+        let mmap = mmap.make_read_only().expect("failed to map memory");
+        let overlays = vec![(32, vec![b'b'; 16]), (8, vec![b'a'; 8])];
+        let mut reader = DvdBndEntryReader::with_overlays(mmap, 40, overlays);
+
+        let expected = [&[b'.'; 8][..], &[b'a'; 8], &[b'.'; 16], &[b'b'; 8]].concat();
+
+        let mut streamed = Vec::new();
+        reader
+            .read_to_end(&mut streamed)
+            .expect("failed to read entry");
+        assert_eq!(streamed, expected);
+        assert_eq!(reader.data(), expected);
+
+        let mut tail = Vec::new();
+        reader.seek(SeekFrom::Start(12)).expect("failed to seek");
+        reader.read_to_end(&mut tail).expect("failed to read entry");
+        assert_eq!(tail, expected[12..]);
+    }
+}
@@ -4,10 +4,18 @@ use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 use dashu::integer::{fast_div::ConstDivisor, UBig};
 use num_modular::Reducer;
 use rayon::prelude::*;
-use rsa::{pkcs1::DecodeRsaPublicKey, traits::PublicKeyParts, RsaPublicKey};
-
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    traits::{PrivateKeyParts, PublicKeyParts},
+    RsaPrivateKey, RsaPublicKey,
+};
+use sha2::{Digest, Sha256};
+
+pub use self::writer::{BhdBuilder, BhdBuilderEntry};
 use crate::io_ext::ReadFormatsExt;
 
+mod writer;
+
 pub struct BhdKey {
     exponent: UBig,
     modulus: UBig,
@@ -34,11 +42,113 @@ impl BhdKey {
     }
 }
 
+/// The private half of a [`BhdKey`], used to encrypt a BHD so that it can be read with the public
+/// key. The keys of the game archives aren't public, so this is only useful with a key pair
+/// generated for a repacked archive.
+pub struct BhdPrivateKey {
+    exponent: UBig,
+    modulus: UBig,
+    public_exponent: UBig,
+    input_size: usize,
+    output_size: usize,
+}
+
+impl BhdPrivateKey {
+    pub fn from_pem(data: &str) -> Result<Self, BhdKeyDecodeError> {
+        let key = RsaPrivateKey::from_pkcs1_pem(data)?;
+
+        Ok(BhdPrivateKey {
+            exponent: UBig::from_be_bytes(&key.d().to_bytes_be()),
+            modulus: UBig::from_be_bytes(&key.n().to_bytes_be()),
+            public_exponent: UBig::from_be_bytes(&key.e().to_bytes_be()),
+            input_size: (key.n().bits() - 1) / 8,
+            output_size: key.size(),
+        })
+    }
+
+    /// The public key matching this key, which decrypts what it encrypts.
+    pub fn public_key(&self) -> BhdKey {
+        BhdKey {
+            exponent: self.public_exponent.clone(),
+            modulus: self.modulus.clone(),
+            input_size: self.output_size,
+            output_size: self.input_size,
+        }
+    }
+
+    /// Encrypt `data` in blocks the way [`Bhd::read_with_format`] decrypts them. The last block is
+    /// padded with zeroes.
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        let num_inputs = data.len().div_ceil(self.input_size);
+        let mut encrypted_data = vec![0u8; num_inputs * self.output_size];
+
+        let divisor = ConstDivisor::new(self.modulus.clone());
+        data.par_chunks(self.input_size)
+            .zip(encrypted_data.par_chunks_mut(self.output_size))
+            .for_each(|(block, encrypted_block)| {
+                let mut input = vec![0u8; self.input_size];
+                input[..block.len()].copy_from_slice(block);
+
+                let encrypted = divisor.pow(UBig::from_be_bytes(&input), &self.exponent);
+                let encrypted = encrypted.to_be_bytes();
+                let padding = self.output_size - encrypted.len();
+
+                encrypted_block[padding..].copy_from_slice(&encrypted);
+            });
+
+        encrypted_data
+    }
+}
+
 pub struct Bhd {
     pub toc: Vec<BhdTocEntry>,
 }
 
-#[derive(Debug)]
+/// Layout of the TOC entries, which changed between games.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BhdFormat {
+    /// Dark Souls III and Sekiro: 32-bit name hashes, with the unpadded size stored last.
+    DarkSouls3,
+
+    /// Elden Ring and Armored Core VI: 64-bit name hashes.
+    #[default]
+    EldenRing,
+}
+
+impl BhdFormat {
+    /// Hash of an entry path as stored in the TOC of this format. The path is normalized first,
+    /// see [`normalize_path`].
+    pub fn hash_path(self, path: &str) -> u64 {
+        let path = normalize_path(path);
+
+        match self {
+            BhdFormat::DarkSouls3 => path.chars().fold(0u32, |hash, next| {
+                hash.wrapping_mul(0x25).wrapping_add(next as u32)
+            }) as u64,
+            BhdFormat::EldenRing => path.chars().fold(0u64, |hash, next| {
+                hash.wrapping_mul(0x85).wrapping_add(next as u64)
+            }),
+        }
+    }
+}
+
+/// The form entry paths are hashed in: lowercase, using `/` as separator and with a leading `/`.
+pub fn normalize_path(path: &str) -> String {
+    let mut normalized = String::with_capacity(path.len() + 1);
+
+    if !path.starts_with('/') && !path.starts_with('\\') {
+        normalized.push('/');
+    }
+
+    normalized.extend(path.chars().map(|ch| match ch {
+        '\\' => '/',
+        _ => ch.to_ascii_lowercase(),
+    }));
+
+    normalized
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BhdTocEntry {
     pub hash: u64,
     pub padded_size: u32,
@@ -46,6 +156,45 @@ pub struct BhdTocEntry {
     pub offset: u64,
     pub aes_key: [u8; 16],
     pub encrypted_ranges: Vec<(i64, i64)>,
+    pub digest: Option<BhdDigest>,
+}
+
+/// SHA-256 hash of parts of an entry, stored in the BHD to detect damaged or modified archives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BhdDigest {
+    pub hash: [u8; 32],
+
+    /// Ranges of the decrypted entry data that are hashed, relative to the start of the entry.
+    /// Unused ranges are stored as `(-1, -1)`.
+    pub ranges: Vec<(i64, i64)>,
+}
+
+impl BhdDigest {
+    /// Hash `ranges` of `data`. Returns `None` if a range lies outside of `data`.
+    pub fn compute(data: &[u8], ranges: Vec<(i64, i64)>) -> Option<Self> {
+        let hash = hash_ranges(data, &ranges)?;
+
+        Some(Self { hash, ranges })
+    }
+
+    /// Whether the hashed ranges of `data` are unchanged. Returns `None` if a range lies outside
+    /// of `data`, which means the entry doesn't belong to this digest either.
+    pub fn matches(&self, data: &[u8]) -> Option<bool> {
+        Some(hash_ranges(data, &self.ranges)? == self.hash)
+    }
+}
+
+fn hash_ranges(data: &[u8], ranges: &[(i64, i64)]) -> Option<[u8; 32]> {
+    let mut hasher = Sha256::new();
+
+    for range in ranges {
+        match *range {
+            (-1, -1) => continue,
+            (start, end) => hasher.update(data.get(start as usize..end as usize)?),
+        }
+    }
+
+    Some(hasher.finalize().into())
 }
 
 #[derive(Debug)]
@@ -59,7 +208,33 @@ pub struct BhdHeader {
 }
 
 impl Bhd {
-    pub fn read<R: Read + Seek>(mut file: R, key: BhdKey) -> Result<Self, std::io::Error> {
+    /// Decrypt and read a BHD in the [`BhdFormat::EldenRing`] format.
+    pub fn read<R: Read + Seek>(file: R, key: BhdKey) -> Result<Self, std::io::Error> {
+        Self::read_with_format(file, key, BhdFormat::EldenRing)
+    }
+
+    /// Decrypt and read a BHD. Unencrypted BHDs, like those of repacked archives, are read as-is.
+    pub fn read_with_format<R: Read + Seek>(
+        file: R,
+        key: BhdKey,
+        format: BhdFormat,
+    ) -> Result<Self, std::io::Error> {
+        Self::from_decrypted(&Self::decrypt(file, &key)?, format)
+    }
+
+    /// Decrypt a BHD without parsing it, e.g. to store the result for [`Bhd::from_decrypted`].
+    /// Unencrypted BHDs are returned as-is.
+    pub fn decrypt<R: Read + Seek>(mut file: R, key: &BhdKey) -> Result<Vec<u8>, std::io::Error> {
+        let mut magic = [0u8; 4];
+        file.seek(SeekFrom::Start(0))?;
+        if file.read_exact(&mut magic).is_ok() && &magic == b"BHD5" {
+            let mut data = Vec::new();
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_end(&mut data)?;
+
+            return Ok(data);
+        }
+
         let file_len = file.seek(SeekFrom::End(0))? as usize;
         let num_inputs = file_len.div_ceil(key.input_size);
 
@@ -82,13 +257,18 @@ impl Bhd {
                 decrypted_block[padding..].copy_from_slice(&decrypted_data);
             });
 
-        let mut reader = Cursor::new(&decrypted_data[..]);
+        Ok(decrypted_data)
+    }
+
+    /// Read an unencrypted BHD.
+    pub fn from_decrypted(data: &[u8], format: BhdFormat) -> Result<Self, std::io::Error> {
+        let mut reader = Cursor::new(data);
         let header = read_header(&mut reader)?;
 
         let toc = if header.is_big_endian {
-            read_toc::<_, BigEndian>(header.buckets as usize, reader)
+            read_toc::<_, BigEndian>(header.buckets as usize, format, reader)
         } else {
-            read_toc::<_, LittleEndian>(header.buckets as usize, reader)
+            read_toc::<_, LittleEndian>(header.buckets as usize, format, reader)
         }?;
 
         Ok(Bhd { toc })
@@ -134,6 +314,7 @@ pub fn read_header<R: Read>(mut reader: R) -> Result<BhdHeader, std::io::Error>
 
 pub fn read_toc<R: Read + Seek, O: ByteOrder>(
     buckets: usize,
+    format: BhdFormat,
     mut reader: R,
 ) -> Result<Vec<BhdTocEntry>, std::io::Error> {
     let mut entries = Vec::new();
@@ -147,14 +328,25 @@ pub fn read_toc<R: Read + Seek, O: ByteOrder>(
         reader.seek(SeekFrom::Start(entry_data_offset as u64))?;
 
         for _ in 0..entry_count {
-            let hash = reader.read_u64::<O>()?;
-            let padded_size = reader.read_u32::<O>()?;
-            let size = reader.read_u32::<O>()?;
+            let (hash, padded_size, mut size) = match format {
+                BhdFormat::DarkSouls3 => {
+                    (reader.read_u32::<O>()? as u64, reader.read_u32::<O>()?, 0)
+                }
+                BhdFormat::EldenRing => (
+                    reader.read_u64::<O>()?,
+                    reader.read_u32::<O>()?,
+                    reader.read_u32::<O>()?,
+                ),
+            };
             let offset = reader.read_u64::<O>()?;
 
-            let _digest_offset = reader.read_u64::<O>()?;
+            let digest_offset = reader.read_u64::<O>()?;
             let encryption_offset = reader.read_u64::<O>()?;
 
+            if format == BhdFormat::DarkSouls3 {
+                size = reader.read_u64::<O>()? as u32;
+            }
+
             let next_file_pos = reader.stream_position()?;
             let mut aes_key = [0u8; 16];
 
@@ -172,6 +364,22 @@ pub fn read_toc<R: Read + Seek, O: ByteOrder>(
                 }
             }
 
+            let digest = if digest_offset != 0 {
+                reader.seek(SeekFrom::Start(digest_offset))?;
+
+                let mut hash = [0u8; 32];
+                reader.read_exact(&mut hash)?;
+
+                let range_count = reader.read_u32::<O>()?;
+                let ranges = (0..range_count)
+                    .map(|_| Ok((reader.read_i64::<O>()?, reader.read_i64::<O>()?)))
+                    .collect::<Result<_, std::io::Error>>()?;
+
+                Some(BhdDigest { hash, ranges })
+            } else {
+                None
+            };
+
             reader.seek(SeekFrom::Start(next_file_pos))?;
 
             entries.push(BhdTocEntry {
@@ -181,6 +389,7 @@ pub fn read_toc<R: Read + Seek, O: ByteOrder>(
                 offset,
                 aes_key,
                 encrypted_ranges,
+                digest,
             });
         }
 
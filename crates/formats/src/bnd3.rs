@@ -0,0 +1,256 @@
+use std::{
+    borrow::Cow,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use byteorder::{ByteOrder, ReadBytesExt, BE, LE};
+use thiserror::Error;
+
+pub use self::writer::{BND3Builder, BND3BuilderEntry};
+use crate::{
+    bnd4::{decompress_entry, extension_suffix, hashable_path, BND4Entry, BinderFormat},
+    dcx::DcxError,
+    io_ext::ReadFormatsExt,
+};
+
+mod writer;
+
+#[derive(Debug, Error)]
+pub enum Bnd3Error {
+    #[error("Could not copy bytes {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Entry data at {offset:#x} with size {size:#x} is out of bounds")]
+    EntryOutOfBounds { offset: u64, size: u64 },
+
+    #[error("Could not decompress entry {0}")]
+    Dcx(#[from] DcxError),
+}
+
+/// The binder format used before BND4, found in Demon's Souls, Dark Souls and many sub-archives of
+/// the later games. Entries follow the same model as [`BND4`](crate::bnd4::BND4).
+#[derive(Debug)]
+pub struct BND3 {
+    pub version: [u8; 8],
+    pub raw_format: u8,
+
+    /// Whether the archive was written for a big-endian platform. Archives with
+    /// [`BinderFormat::BIG_ENDIAN`] set are read as big-endian regardless.
+    pub big_endian: bool,
+
+    /// Whether the format and entry flags are stored in big-endian bit order.
+    pub bit_big_endian: bool,
+    pub file_count: u32,
+    pub file_headers_end: u32,
+    pub unk18: i32,
+    pub files: Vec<BND3Entry>,
+    pub data: Vec<u8>,
+}
+
+impl BND3 {
+    pub fn from_reader<R: Read + Seek>(mut r: R) -> Result<Self, Bnd3Error> {
+        r.read_magic(b"BND3")?;
+
+        let mut version = [0u8; 8];
+        r.read_exact(&mut version)?;
+
+        let raw_format = r.read_u8()?;
+        let big_endian = r.read_bool()?;
+        let bit_big_endian = r.read_bool()?;
+        r.read_padding(1)?;
+
+        let format = BinderFormat::from_raw(raw_format, bit_big_endian);
+        let header = Header {
+            version,
+            raw_format,
+            big_endian,
+            bit_big_endian,
+        };
+
+        if big_endian || format.0 & BinderFormat::BIG_ENDIAN != 0 {
+            Self::from_reader_with::<BE, R>(r, header, format)
+        } else {
+            Self::from_reader_with::<LE, R>(r, header, format)
+        }
+    }
+
+    fn from_reader_with<BO: ByteOrder, R: Read + Seek>(
+        mut r: R,
+        header: Header,
+        format: BinderFormat,
+    ) -> Result<Self, Bnd3Error> {
+        let file_count = r.read_u32::<BO>()?;
+        let file_headers_end = r.read_u32::<BO>()?;
+        let unk18 = r.read_i32::<BO>()?;
+        r.read_padding(4)?;
+
+        let mut files = vec![];
+        for _ in 0..file_count {
+            files.push(BND3Entry::from_reader::<BO, _>(
+                &mut r,
+                format,
+                header.bit_big_endian,
+            )?);
+        }
+
+        let mut data = vec![];
+        r.seek(SeekFrom::Start(0))?;
+        r.read_to_end(&mut data)?;
+
+        // Checked once here so that entry data can be sliced without further bounds checks.
+        for file in &files {
+            let end = file.data_offset.checked_add(file.compressed_size);
+            if end.is_none_or(|end| end > data.len() as u64) {
+                return Err(Bnd3Error::EntryOutOfBounds {
+                    offset: file.data_offset,
+                    size: file.compressed_size,
+                });
+            }
+        }
+
+        Ok(Self {
+            version: header.version,
+            raw_format: header.raw_format,
+            big_endian: header.big_endian,
+            bit_big_endian: header.bit_big_endian,
+            file_count,
+            file_headers_end,
+            unk18,
+            files,
+            data,
+        })
+    }
+
+    /// The layout flags of this archive, decoded from [`BND3::raw_format`].
+    pub fn format(&self) -> BinderFormat {
+        BinderFormat::from_raw(self.raw_format, self.bit_big_endian)
+    }
+
+    /// The entry data as stored in the archive. `handle` must be one of this archive's entries.
+    pub fn file_bytes(&self, handle: &BND3Entry) -> &[u8] {
+        let start = handle.data_offset as usize;
+        let end = start + handle.compressed_size as usize;
+
+        &self.data[start..end]
+    }
+
+    /// Contents of an entry, decompressed first if the archive stores it compressed.
+    pub fn file_contents(&self, handle: &BND3Entry) -> Result<Cow<'_, [u8]>, Bnd3Error> {
+        let bytes = self.file_bytes(handle);
+        if !handle.is_compressed() {
+            return Ok(Cow::Borrowed(bytes));
+        }
+
+        Ok(Cow::Owned(decompress_entry(
+            bytes,
+            handle.uncompressed_size,
+        )?))
+    }
+
+    pub fn file_by_id(&self, id: u32) -> Option<&BND3Entry> {
+        self.files.iter().find(|file| file.id == id)
+    }
+
+    /// Look up an entry by its full path. Paths are compared with [`hashable_path`], so case and
+    /// separators don't matter.
+    pub fn file_by_path(&self, path: &str) -> Option<&BND3Entry> {
+        let path = hashable_path(path);
+
+        self.files
+            .iter()
+            .find(|file| hashable_path(&file.path) == path)
+    }
+
+    /// Entries with a path ending in `extension`, e.g. `flver` or `.anibnd.dcx`, ignoring case.
+    pub fn files_by_extension<'s>(
+        &'s self,
+        extension: &str,
+    ) -> impl Iterator<Item = &'s BND3Entry> + 's {
+        let suffix = extension_suffix(extension);
+
+        self.files
+            .iter()
+            .filter(move |file| file.path.to_lowercase().ends_with(&suffix))
+    }
+}
+
+/// Fields read before the byte order of the rest of the archive is known.
+struct Header {
+    version: [u8; 8],
+    raw_format: u8,
+    big_endian: bool,
+    bit_big_endian: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BND3Entry {
+    /// Entry flags as stored in the archive, see [`BinderFormat::entry_flags`].
+    pub flags: u8,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub data_offset: u64,
+    pub id: u32,
+    pub path: String,
+    compressed: bool,
+}
+
+impl BND3Entry {
+    pub fn from_reader<BO: ByteOrder, R: Read + Seek>(
+        mut r: R,
+        format: BinderFormat,
+        bit_big_endian: bool,
+    ) -> Result<Self, Bnd3Error> {
+        let flags = r.read_u8()?;
+        let compressed =
+            format.entry_flags(flags, bit_big_endian) & BND4Entry::FLAG_COMPRESSED != 0;
+        r.read_padding(3)?;
+
+        let compressed_size = r.read_u32::<BO>()? as u64;
+        let data_offset = if format.has_long_offsets() {
+            r.read_u64::<BO>()?
+        } else {
+            r.read_u32::<BO>()? as u64
+        };
+
+        let id = if format.has_ids() {
+            r.read_u32::<BO>()?
+        } else {
+            u32::MAX
+        };
+
+        let path = if format.has_names() {
+            let name_offset = r.read_u32::<BO>()?;
+
+            let current = r.stream_position()?;
+            r.seek(SeekFrom::Start(name_offset as u64))?;
+            let path = r.read_shift_jis()?;
+            r.seek(SeekFrom::Start(current))?;
+
+            path
+        } else {
+            String::new()
+        };
+
+        let uncompressed_size = if format.has_compression() {
+            r.read_u32::<BO>()? as u64
+        } else {
+            compressed_size
+        };
+
+        Ok(Self {
+            flags,
+            compressed_size,
+            uncompressed_size,
+            data_offset,
+            id,
+            path,
+            compressed,
+        })
+    }
+
+    /// Whether the entry data is stored compressed, usually as a DCX container. Use
+    /// [`BND3::file_contents`] to get the decompressed data.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+
+use byteorder::{ByteOrder, WriteBytesExt, BE, LE};
+
+use crate::{bnd3::BND3, bnd4::BinderFormat, io_ext::WriteFormatsExt};
+
+const HEADER_SIZE: u64 = 0x20;
+const DATA_ALIGNMENT: u64 = 0x10;
+
+/// Entry flags used by the game for regular files, as stored in little-endian bit order.
+const DEFAULT_ENTRY_FLAGS: u8 = 0x40;
+
+/// A file that will be written to a BND3 archive by a [`BND3Builder`].
+#[derive(Clone, Debug)]
+pub struct BND3BuilderEntry<'a> {
+    pub flags: u8,
+    pub id: u32,
+    pub path: String,
+
+    /// Size of the entry contents once decompressed. Equal to the length of [`data`] for
+    /// uncompressed entries.
+    ///
+    /// [`data`]: BND3BuilderEntry::data
+    pub uncompressed_size: u64,
+
+    /// Entry contents as they will be stored in the archive.
+    pub data: Cow<'a, [u8]>,
+}
+
+impl<'a> BND3BuilderEntry<'a> {
+    /// Replace the contents of this entry with uncompressed data.
+    pub fn set_data<D: Into<Cow<'a, [u8]>>>(&mut self, data: D) {
+        self.data = data.into();
+        self.uncompressed_size = self.data.len() as u64;
+    }
+}
+
+/// Lays out and serializes a BND3 archive, see [`BND4Builder`](crate::bnd4::BND4Builder).
+#[derive(Clone, Debug)]
+pub struct BND3Builder<'a> {
+    pub version: [u8; 8],
+    pub raw_format: u8,
+    pub big_endian: bool,
+    pub bit_big_endian: bool,
+    pub unk18: i32,
+    pub files: Vec<BND3BuilderEntry<'a>>,
+}
+
+impl<'a> Default for BND3Builder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> BND3Builder<'a> {
+    /// Create an empty builder using the header values found in Dark Souls archives.
+    pub fn new() -> Self {
+        Self {
+            version: *b"07D7R6\0\0",
+            raw_format: 0x74,
+            big_endian: false,
+            bit_big_endian: false,
+            unk18: 0,
+            files: Vec::new(),
+        }
+    }
+
+    /// Create a builder from the header and entries of an existing archive, borrowing the entry
+    /// contents from it.
+    pub fn from_bnd3(bnd: &'a BND3) -> Self {
+        Self {
+            version: bnd.version,
+            raw_format: bnd.raw_format,
+            big_endian: bnd.big_endian,
+            bit_big_endian: bnd.bit_big_endian,
+            unk18: bnd.unk18,
+            files: bnd
+                .files
+                .iter()
+                .map(|file| BND3BuilderEntry {
+                    flags: file.flags,
+                    id: file.id,
+                    path: file.path.clone(),
+                    uncompressed_size: file.uncompressed_size,
+                    data: Cow::Borrowed(bnd.file_bytes(file)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Append an uncompressed file to the archive.
+    pub fn add_file<P: Into<String>, D: Into<Cow<'a, [u8]>>>(
+        &mut self,
+        id: u32,
+        path: P,
+        data: D,
+    ) -> &mut Self {
+        let data = data.into();
+
+        self.files.push(BND3BuilderEntry {
+            flags: DEFAULT_ENTRY_FLAGS,
+            id,
+            path: path.into(),
+            uncompressed_size: data.len() as u64,
+            data,
+        });
+
+        self
+    }
+
+    pub fn format(&self) -> BinderFormat {
+        BinderFormat::from_raw(self.raw_format, self.bit_big_endian)
+    }
+
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        if self.big_endian || self.format().0 & BinderFormat::BIG_ENDIAN != 0 {
+            self.write_with::<BE, W>(w)
+        } else {
+            self.write_with::<LE, W>(w)
+        }
+    }
+
+    fn write_with<BO: ByteOrder, W: Write>(&self, mut w: W) -> io::Result<()> {
+        let format = self.format();
+        let names_offset = HEADER_SIZE + format.bnd3_entry_header_size() * self.files.len() as u64;
+
+        let mut names = Vec::new();
+        let mut name_offsets = Vec::with_capacity(self.files.len());
+
+        if format.has_names() {
+            for file in &self.files {
+                name_offsets.push(to_u32(names_offset + names.len() as u64)?);
+                names.write_shift_jis(&file.path)?;
+            }
+        }
+
+        let file_headers_end = names_offset + names.len() as u64;
+
+        let mut position = file_headers_end;
+        let data_offsets: Vec<u64> = self
+            .files
+            .iter()
+            .map(|file| {
+                if !file.data.is_empty() {
+                    position = position.next_multiple_of(DATA_ALIGNMENT);
+                }
+
+                let offset = position;
+                position += file.data.len() as u64;
+
+                offset
+            })
+            .collect();
+
+        w.write_all(b"BND3")?;
+        w.write_all(&self.version)?;
+        w.write_u8(self.raw_format)?;
+        w.write_u8(self.big_endian as u8)?;
+        w.write_u8(self.bit_big_endian as u8)?;
+        w.write_padding(1)?;
+        w.write_u32::<BO>(self.files.len() as u32)?;
+        w.write_u32::<BO>(to_u32(file_headers_end)?)?;
+        w.write_i32::<BO>(self.unk18)?;
+        w.write_padding(4)?;
+
+        for (index, file) in self.files.iter().enumerate() {
+            w.write_u8(file.flags)?;
+            w.write_padding(3)?;
+            w.write_u32::<BO>(to_u32(file.data.len() as u64)?)?;
+
+            if format.has_long_offsets() {
+                w.write_u64::<BO>(data_offsets[index])?;
+            } else {
+                w.write_u32::<BO>(to_u32(data_offsets[index])?)?;
+            }
+
+            if format.has_ids() {
+                w.write_u32::<BO>(file.id)?;
+            }
+
+            if format.has_names() {
+                w.write_u32::<BO>(name_offsets[index])?;
+            }
+
+            if format.has_compression() {
+                w.write_u32::<BO>(to_u32(file.uncompressed_size)?)?;
+            }
+        }
+
+        w.write_all(&names)?;
+
+        let mut position = file_headers_end;
+        for (file, offset) in self.files.iter().zip(data_offsets) {
+            w.write_padding((offset - position) as usize)?;
+            w.write_all(&file.data)?;
+
+            position = offset + file.data.len() as u64;
+        }
+
+        Ok(())
+    }
+}
+
+impl BND3 {
+    /// Serialize this archive. Unless entries were modified the output is identical to the bytes
+    /// it was read from.
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        BND3Builder::from_bnd3(self).write(w)
+    }
+}
+
+fn to_u32(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::other("archive is too large for BND3"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::BND3Builder;
+    use crate::bnd3::BND3;
+
+    #[test]
+    fn round_trips() {
+        for big_endian in [false, true] {
+            let mut builder = BND3Builder::new();
+            builder.big_endian = big_endian;
+            builder
+                .add_file(0, "N:\\FRPG\\data\\c0000.flver", vec![0x1u8; 37])
+                .add_file(1, "N:\\FRPG\\data\\c0000.tpf", vec![0x2u8; 5]);
+
+            let mut written = Vec::new();
+            builder.write(&mut written).expect("failed to write BND3");
+
+            let bnd = BND3::from_reader(Cursor::new(&written)).expect("failed to read BND3");
+            assert_eq!(bnd.files.len(), 2);
+            assert_eq!(bnd.files[1].path, "N:\\FRPG\\data\\c0000.tpf");
+            assert_eq!(bnd.file_bytes(&bnd.files[0]), &[0x1u8; 37][..]);
+            assert_eq!(bnd.file_by_id(1), Some(&bnd.files[1]));
+
+            let mut rewritten = Vec::new();
+            bnd.write(&mut rewritten).expect("failed to rewrite BND3");
+
+            assert_eq!(written, rewritten);
+        }
+    }
+}
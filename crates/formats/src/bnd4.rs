@@ -1,15 +1,63 @@
-use std::io::{self, Read, Seek, SeekFrom};
+use std::{
+    borrow::Cow,
+    io::{self, Read, Seek, SeekFrom},
+};
 
-use byteorder::{ReadBytesExt, LE};
+use byteorder::{ByteOrder, ReadBytesExt, BE, LE};
+use thiserror::Error;
 
-use crate::io_ext::ReadFormatsExt;
+pub(crate) use self::writer::is_prime;
+pub use self::{
+    view::{Bnd4, Bnd4File},
+    writer::{BND4Builder, BND4BuilderEntry},
+};
+use crate::{
+    dcx::{deflate::DeflateDecoder, DcxError, DcxHeader, MAX_COMPRESSION_RATIO},
+    io_ext::ReadFormatsExt,
+};
+
+pub(crate) mod buckets;
+mod view;
+mod writer;
 
 type BND4Reader = std::io::Cursor<Vec<u8>>;
 
+#[derive(Debug, Error)]
+pub enum Bnd4Error {
+    #[error("Could not copy bytes {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Not a BND4 archive")]
+    InvalidMagic,
+
+    #[error("Could not read entry name")]
+    InvalidName,
+
+    #[error("Archive ends before the end of its headers")]
+    Truncated,
+
+    #[error("Entry header size {0:#x} is too small for the archive format")]
+    InvalidHeaderSize(u64),
+
+    #[error("Entry {index} is out of range for an archive with {len} entries")]
+    IndexOutOfRange { index: usize, len: usize },
+
+    #[error("Entry data at {offset:#x} with size {size:#x} is out of bounds")]
+    EntryOutOfBounds { offset: u64, size: u64 },
+
+    #[error("Could not decompress entry {0}")]
+    Dcx(#[from] DcxError),
+}
+
 #[derive(Debug)]
 pub struct BND4 {
     pub unk04: u8,
     pub unk05: u8,
+
+    /// Whether the archive was written for a big-endian platform (PS3 and Xbox 360).
+    pub big_endian: bool,
+
+    /// Zero when the format and entry flags are stored in big-endian bit order.
     pub unk0a: u8,
     pub file_count: u32,
     pub file_headers_offset: u64,
@@ -25,43 +73,192 @@ pub struct BND4 {
 }
 
 impl BND4 {
-    pub fn from_reader<R: Read + Seek>(mut r: R) -> io::Result<Self> {
-        r.read_magic(b"BND4")?;
+    pub fn from_reader<R: Read + Seek>(mut r: R) -> Result<Self, Bnd4Error> {
+        let (header, files) = Binder4Header::read_with_entries(&mut r, b"BND4")?;
+
+        let mut data = vec![];
+        r.seek(SeekFrom::Start(0))?;
+        r.read_to_end(&mut data)?;
+
+        check_entry_bounds(&files, &data)?;
+
+        Ok(Self {
+            unk04: header.unk04,
+            unk05: header.unk05,
+            big_endian: header.big_endian,
+            unk0a: header.unk0a,
+            file_count: header.file_count,
+            file_headers_offset: header.file_headers_offset,
+            version: header.version,
+            file_header_size: header.file_header_size,
+            file_headers_end: header.file_headers_end,
+            unicode: header.unicode,
+            raw_format: header.raw_format,
+            extended: header.extended,
+            buckets_offset: header.buckets_offset,
+            files,
+            data,
+        })
+    }
+
+    /// The layout flags of this archive, decoded from [`BND4::raw_format`].
+    pub fn format(&self) -> BinderFormat {
+        BinderFormat::from_raw(self.raw_format, self.unk0a == 0)
+    }
+
+    /// The entry data as stored in the archive. `handle` must be one of this archive's entries.
+    pub fn file_bytes(&self, handle: &BND4Entry) -> &[u8] {
+        let start = handle.data_offset as usize;
+        let end = start + handle.compressed_size as usize;
+
+        &self.data[start..end]
+    }
+
+    /// Contents of an entry, decompressed first if the archive stores it compressed.
+    pub fn file_contents(&self, handle: &BND4Entry) -> Result<Cow<'_, [u8]>, Bnd4Error> {
+        let bytes = self.file_bytes(handle);
+        if !handle.is_compressed() {
+            return Ok(Cow::Borrowed(bytes));
+        }
+
+        Ok(Cow::Owned(decompress_entry(
+            bytes,
+            handle.uncompressed_size,
+        )?))
+    }
+
+    pub fn file_by_id(&self, id: u32) -> Option<&BND4Entry> {
+        self.files.iter().find(|file| file.id == id)
+    }
+
+    /// Look up an entry by its full path, e.g.
+    /// `N:\\GR\\data\\INTERROOT_win64\\chr\\c0000\\c0000.flver`. Paths are compared with
+    /// [`hashable_path`], so case and separators don't matter. Uses the hash bucket table of
+    /// the archive when it has one.
+    pub fn file_by_path(&self, path: &str) -> Option<&BND4Entry> {
+        let path = hashable_path(path);
+        let matches = |file: &&BND4Entry| hashable_path(&file.path) == path;
+
+        let buckets_offset = if self.extended == 4 {
+            self.buckets_offset
+        } else {
+            0
+        };
+
+        match buckets::candidates(&self.data, self.big_endian, buckets_offset, &path) {
+            Some(candidates) => candidates
+                .into_iter()
+                .filter_map(|index| self.files.get(index))
+                .find(matches),
+            None => self.files.iter().find(matches),
+        }
+    }
+
+    /// Entries with a path ending in `extension`, e.g. `flver` or `.anibnd.dcx`, ignoring case.
+    pub fn files_by_extension<'s>(
+        &'s self,
+        extension: &str,
+    ) -> impl Iterator<Item = &'s BND4Entry> + 's {
+        let suffix = extension_suffix(extension);
+
+        self.files
+            .iter()
+            .filter(move |file| file.path.to_lowercase().ends_with(&suffix))
+    }
+
+    /// Find the first entry with the same file stem as `path`. Archives often contain several
+    /// files with the same stem, prefer [`BND4::file_by_path`] where possible.
+    pub fn file_descriptor_by_stem(&self, path: &str) -> Option<&BND4Entry> {
+        let lookup = std::path::PathBuf::from(Self::normalize_path(path));
+
+        self.files.iter().find(|f| {
+            let path = std::path::PathBuf::from(Self::normalize_path(&f.path));
+
+            path.file_stem() == lookup.file_stem()
+        })
+    }
+
+    pub fn normalize_path(path: &str) -> String {
+        path.replace("N:\\", "").to_lowercase().replace('\\', "/")
+    }
+}
+
+/// Header fields shared by BND4 archives and the BHF4 headers of split archives, which only differ
+/// in their magic and where the entry data is stored.
+pub(crate) struct Binder4Header {
+    pub unk04: u8,
+    pub unk05: u8,
+    pub big_endian: bool,
+    pub unk0a: u8,
+    pub file_count: u32,
+    pub file_headers_offset: u64,
+    pub version: u64,
+    pub file_header_size: u64,
+    pub file_headers_end: u64,
+    pub unicode: bool,
+    pub raw_format: u8,
+    pub extended: u8,
+    pub buckets_offset: u64,
+}
+
+impl Binder4Header {
+    /// Read the header and entry headers of a binder starting with `magic`.
+    pub(crate) fn read_with_entries<R: Read + Seek>(
+        mut r: R,
+        magic: &[u8; 4],
+    ) -> Result<(Self, Vec<BND4Entry>), Bnd4Error> {
+        check_magic(&mut r, magic)?;
 
         let unk04 = r.read_u8()?;
         let unk05 = r.read_u8()?;
         r.read_padding(3)?;
 
-        assert!(r.read_u8()? == 0x0, "BND4 is not little endian");
+        if r.read_bool()? {
+            Self::read_with::<BE, R>(r, unk04, unk05, true)
+        } else {
+            Self::read_with::<LE, R>(r, unk04, unk05, false)
+        }
+    }
 
+    fn read_with<BO: ByteOrder, R: Read + Seek>(
+        mut r: R,
+        unk04: u8,
+        unk05: u8,
+        big_endian: bool,
+    ) -> Result<(Self, Vec<BND4Entry>), Bnd4Error> {
         let unk0a = r.read_u8()?;
         r.read_padding(1)?;
-        let file_count = r.read_u32::<LE>()?;
+        let file_count = r.read_u32::<BO>()?;
 
-        let file_headers_offset = r.read_u64::<LE>()?;
-        let version = r.read_u64::<LE>()?;
-        let file_header_size = r.read_u64::<LE>()?;
-        let file_headers_end = r.read_u64::<LE>()?;
+        let file_headers_offset = r.read_u64::<BO>()?;
+        let version = r.read_u64::<BO>()?;
+        let file_header_size = r.read_u64::<BO>()?;
+        let file_headers_end = r.read_u64::<BO>()?;
         let unicode = r.read_u8()? == 0x1;
         let raw_format = r.read_u8()?;
         let extended = r.read_u8()?;
 
         r.read_padding(5)?;
 
-        let buckets_offset = r.read_u64::<LE>()?;
+        let buckets_offset = r.read_u64::<BO>()?;
+        let format = BinderFormat::from_raw(raw_format, unk0a == 0);
+
+        r.seek(SeekFrom::Start(file_headers_offset))?;
 
         let mut files = vec![];
         for _ in 0..file_count {
-            files.push(BND4Entry::from_reader(&mut r)?);
+            files.push(BND4Entry::from_reader::<BO, _>(
+                &mut r,
+                format,
+                unk0a == 0,
+                unicode,
+            )?);
         }
 
-        let mut data = vec![];
-        r.seek(SeekFrom::Start(0))?;
-        r.read_to_end(&mut data)?;
-
-        Ok(Self {
+        let header = Self {
             unk04,
             unk05,
+            big_endian,
             unk0a,
             file_count,
             file_headers_offset,
@@ -72,65 +269,211 @@ impl BND4 {
             raw_format,
             extended,
             buckets_offset,
-            files,
-            data,
-        })
+        };
+
+        Ok((header, files))
     }
+}
 
-    pub fn file_bytes(&self, handle: &BND4Entry) -> &[u8] {
-        let start = handle.data_offset as usize;
-        let end = start + handle.compressed_size as usize;
+/// Read the magic at the start of a binder, failing with [`Bnd4Error::InvalidMagic`] unless it
+/// matches `magic`.
+pub(crate) fn check_magic<R: Read>(mut r: R, magic: &[u8; 4]) -> Result<(), Bnd4Error> {
+    let mut buffer = [0u8; 4];
+    r.read_exact(&mut buffer)?;
 
-        &self.data[start..end]
+    if &buffer != magic {
+        return Err(Bnd4Error::InvalidMagic);
     }
 
-    pub fn file_descriptor_by_stem(&self, path: &str) -> Option<&BND4Entry> {
-        let lookup = std::path::PathBuf::from(Self::normalize_path(path));
+    Ok(())
+}
 
-        self.files.iter().find(|f| {
-            let path = std::path::PathBuf::from(Self::normalize_path(&f.path));
+/// Checked once after parsing so that entry data can be sliced without further bounds checks.
+pub(crate) fn check_entry_bounds(files: &[BND4Entry], data: &[u8]) -> Result<(), Bnd4Error> {
+    for file in files {
+        let end = file.data_offset.checked_add(file.compressed_size);
+        if end.is_none_or(|end| end > data.len() as u64) {
+            return Err(Bnd4Error::EntryOutOfBounds {
+                offset: file.data_offset,
+                size: file.compressed_size,
+            });
+        }
+    }
 
-            path.file_stem() == lookup.file_stem()
-        })
+    Ok(())
+}
+
+/// Flags describing which fields are present in the entry headers of a binder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BinderFormat(pub u8);
+
+impl BinderFormat {
+    pub const BIG_ENDIAN: u8 = 0b0000_0001;
+    pub const IDS: u8 = 0b0000_0010;
+    pub const NAMES1: u8 = 0b0000_0100;
+    pub const NAMES2: u8 = 0b0000_1000;
+    pub const LONG_OFFSETS: u8 = 0b0001_0000;
+    pub const COMPRESSION: u8 = 0b0010_0000;
+    pub const FLAG7: u8 = 0b1000_0000;
+
+    /// Decode a format byte as stored in a binder header. Unless the binder uses big-endian bit
+    /// order the flags are stored bit-reversed, which can usually be detected from the raw value
+    /// alone.
+    pub fn from_raw(raw: u8, bit_big_endian: bool) -> Self {
+        let reverse = bit_big_endian || (raw & 0b1 != 0 && raw & 0b1000_0000 == 0);
+
+        Self(if reverse { raw } else { raw.reverse_bits() })
     }
 
-    pub fn normalize_path(path: &str) -> String {
-        path.replace("N:\\", "").to_lowercase().replace('\\', "/")
+    /// Decode the flags of an entry header. Like the format itself these are usually stored
+    /// bit-reversed.
+    pub fn entry_flags(&self, raw: u8, bit_big_endian: bool) -> u8 {
+        let reverse = bit_big_endian || (self.has_compression() && self.0 & Self::FLAG7 != 0);
+
+        if reverse {
+            raw
+        } else {
+            raw.reverse_bits()
+        }
+    }
+
+    pub fn has_ids(&self) -> bool {
+        self.0 & Self::IDS != 0
+    }
+
+    pub fn has_names(&self) -> bool {
+        self.0 & (Self::NAMES1 | Self::NAMES2) != 0
+    }
+
+    pub fn has_long_offsets(&self) -> bool {
+        self.0 & Self::LONG_OFFSETS != 0
+    }
+
+    pub fn has_compression(&self) -> bool {
+        self.0 & Self::COMPRESSION != 0
+    }
+
+    /// Archives with only [`BinderFormat::NAMES1`] set store their IDs after the name offset,
+    /// followed by 4 bytes of padding.
+    fn has_trailing_id(&self) -> bool {
+        self.0 == Self::NAMES1
+    }
+
+    /// Size of a single entry header in a BND3 archive using this format.
+    pub fn bnd3_entry_header_size(&self) -> u64 {
+        let mut size = 0x8;
+
+        size += if self.has_long_offsets() { 0x8 } else { 0x4 };
+
+        if self.has_ids() {
+            size += 0x4;
+        }
+
+        if self.has_names() {
+            size += 0x4;
+        }
+
+        if self.has_compression() {
+            size += 0x4;
+        }
+
+        size
+    }
+
+    /// Size of a single entry header in a BND4 archive using this format.
+    pub fn bnd4_entry_header_size(&self) -> u64 {
+        let mut size = 0x10;
+
+        if self.has_compression() {
+            size += 0x8;
+        }
+
+        size += if self.has_long_offsets() { 0x8 } else { 0x4 };
+
+        if self.has_ids() {
+            size += 0x4;
+        }
+
+        if self.has_names() {
+            size += 0x4;
+        }
+
+        if self.has_trailing_id() {
+            size += 0x8;
+        }
+
+        size
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct BND4Entry {
+    /// Entry flags as stored in the archive, see [`BinderFormat::entry_flags`].
     pub flags: u8,
     pub unk4: i32,
     pub compressed_size: u64,
     pub uncompressed_size: u64,
-    pub data_offset: u32,
+    pub data_offset: u64,
     pub id: u32,
     pub path: String,
+    compressed: bool,
 }
 
 impl BND4Entry {
-    pub fn from_reader<R: Read + Seek>(mut r: R) -> Result<Self, io::Error> {
+    /// Set in the decoded entry flags when the entry data is stored compressed.
+    pub const FLAG_COMPRESSED: u8 = 0b0000_0001;
+
+    pub fn from_reader<BO: ByteOrder, R: Read + Seek>(
+        mut r: R,
+        format: BinderFormat,
+        bit_big_endian: bool,
+        unicode: bool,
+    ) -> Result<Self, Bnd4Error> {
         let flags = r.read_u8()?;
+        let compressed = format.entry_flags(flags, bit_big_endian) & Self::FLAG_COMPRESSED != 0;
         r.read_padding(3)?;
 
-        let unk4 = r.read_i32::<LE>()?;
-        let compressed_size = r.read_u64::<LE>()?;
-        let uncompressed_size = r.read_u64::<LE>()?;
-        let data_offset = r.read_u32::<LE>()?;
-        let id = r.read_u32::<LE>()?;
-        let name_offset = r.read_u32::<LE>()?;
+        let unk4 = r.read_i32::<BO>()?;
+        let compressed_size = r.read_u64::<BO>()?;
+        let uncompressed_size = if format.has_compression() {
+            r.read_u64::<BO>()?
+        } else {
+            compressed_size
+        };
+
+        let data_offset = if format.has_long_offsets() {
+            r.read_u64::<BO>()?
+        } else {
+            r.read_u32::<BO>()? as u64
+        };
+
+        let mut id = if format.has_ids() {
+            r.read_u32::<BO>()?
+        } else {
+            u32::MAX
+        };
 
-        let current = r.stream_position()?;
-        r.seek(SeekFrom::Start(name_offset as u64))?;
-        let path = r.read_utf16::<LE>()?;
-        r.seek(SeekFrom::Start(current))?;
+        let path = if format.has_names() {
+            let name_offset = r.read_u32::<BO>()?;
 
-        assert!(
-            compressed_size == uncompressed_size,
-            "BND4 entry compression detected"
-        );
+            let current = r.stream_position()?;
+            r.seek(SeekFrom::Start(name_offset as u64))?;
+            let path = if unicode {
+                r.read_utf16::<BO>()?
+            } else {
+                r.read_shift_jis()?
+            };
+            r.seek(SeekFrom::Start(current))?;
+
+            path
+        } else {
+            String::new()
+        };
+
+        if format.has_trailing_id() {
+            id = r.read_u32::<BO>()?;
+            r.read_padding(4)?;
+        }
 
         Ok(Self {
             flags,
@@ -140,14 +483,206 @@ impl BND4Entry {
             data_offset,
             id,
             path,
+            compressed,
         })
     }
 
+    /// Whether the entry data is stored compressed, usually as a DCX container. Use
+    /// [`BND4::file_contents`] to get the decompressed data.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// The entry data as stored in the archive, which is not decompressed.
     pub fn bytes(&self, r: &mut BND4Reader) -> Result<Vec<u8>, io::Error> {
         let mut buffer = vec![0x0u8; self.compressed_size as usize];
-        r.seek(SeekFrom::Start(self.data_offset as u64))?;
+        r.seek(SeekFrom::Start(self.data_offset))?;
         r.read_exact(&mut buffer)?;
 
         Ok(buffer)
     }
 }
+
+/// Decompress the data of an entry stored with [`BND4Entry::FLAG_COMPRESSED`], usually a DCX
+/// container. Fails unless the data decompresses to exactly `uncompressed_size` bytes.
+pub(crate) fn decompress_entry(bytes: &[u8], uncompressed_size: u64) -> Result<Vec<u8>, DcxError> {
+    let contents = if DcxHeader::has_magic(bytes) {
+        DcxHeader::decompress_to_vec(bytes)?.1
+    } else {
+        // The size comes from the entry header, so don't reserve more than the data could
+        // plausibly expand to and stop reading once it's exceeded.
+        let capacity = uncompressed_size.min(bytes.len() as u64 * MAX_COMPRESSION_RATIO);
+        let mut contents = Vec::with_capacity(capacity as usize);
+        DeflateDecoder::new(bytes)
+            .take(uncompressed_size.saturating_add(1))
+            .read_to_end(&mut contents)?;
+
+        contents
+    };
+
+    if contents.len() as u64 != uncompressed_size {
+        return Err(DcxError::UncompressedSizeMismatch {
+            expected: uncompressed_size,
+            actual: contents.len() as u64,
+        });
+    }
+
+    Ok(contents)
+}
+
+/// Hash of an entry path as stored in the bucket table of a BND4 archive, computed over the path
+/// as normalized by [`hashable_path`].
+pub fn path_hash(path: &str) -> u32 {
+    hashable_path(path)
+        .chars()
+        .fold(0u32, |hash, c| hash.wrapping_mul(37).wrapping_add(c as u32))
+}
+
+/// Normalize an entry path the way the games do before hashing it: lowercase, with forward
+/// slashes and always starting with a `/`. Paths that normalize to the same value refer to the
+/// same entry.
+pub fn hashable_path(path: &str) -> String {
+    let path = path.trim().replace('\\', "/").to_lowercase();
+
+    if path.starts_with('/') {
+        path
+    } else {
+        format!("/{path}")
+    }
+}
+
+/// Lowercase suffix matched against entry paths when looking up entries by extension.
+pub(crate) fn extension_suffix(extension: &str) -> String {
+    format!(".{}", extension.trim_start_matches('.').to_lowercase())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::{BND4Builder, BND4BuilderEntry, BND4Entry, Bnd4, Bnd4Error, BND4};
+    use crate::dcx::{DcxAlgorithm, DcxEncoder, DcxError};
+
+    /// Decoded flags of a regular file stored compressed.
+    const COMPRESSED_FLAGS: u8 = 0b10 | BND4Entry::FLAG_COMPRESSED;
+
+    fn contents() -> Vec<u8> {
+        (0..0x1000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// An archive with a DCX compressed entry, a raw zlib entry and an uncompressed entry, with the
+    /// format and entry flags stored in the given bit order.
+    fn compressed_archive(bit_big_endian: bool) -> Vec<u8> {
+        let contents = contents();
+
+        let dcx = DcxEncoder::new(DcxAlgorithm::Deflate)
+            .encode_to_vec(&contents)
+            .expect("failed to compress DCX");
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(&contents).expect("failed to compress zlib");
+        let zlib = zlib.finish().expect("failed to compress zlib");
+
+        let mut builder = BND4Builder::new();
+        if bit_big_endian {
+            builder.unk0a = 0;
+            builder.raw_format = builder.raw_format.reverse_bits();
+        }
+
+        let flags = if bit_big_endian {
+            COMPRESSED_FLAGS
+        } else {
+            COMPRESSED_FLAGS.reverse_bits()
+        };
+
+        for (id, data) in [dcx, zlib].into_iter().enumerate() {
+            builder.files.push(BND4BuilderEntry {
+                flags,
+                unk4: -1,
+                id: id as u32,
+                path: format!("N:\\GR\\data\\c000{id}.flver"),
+                uncompressed_size: contents.len() as u64,
+                data: data.into(),
+                data_offset: None,
+            });
+        }
+        builder.add_file(2, "N:\\GR\\data\\c0002.tpf", vec![0x2u8; 5]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        written
+    }
+
+    #[test]
+    fn decompresses_entries() {
+        for bit_big_endian in [false, true] {
+            let bytes = compressed_archive(bit_big_endian);
+
+            let bnd = BND4::from_reader(Cursor::new(&bytes)).expect("failed to read BND4");
+            assert!(bnd.format().has_compression());
+
+            for file in &bnd.files[..2] {
+                assert!(file.is_compressed());
+                assert_eq!(file.uncompressed_size, contents().len() as u64);
+
+                let decompressed = bnd.file_contents(file).expect("failed to decompress");
+                assert_eq!(decompressed, contents());
+            }
+
+            assert!(!bnd.files[2].is_compressed());
+            let uncompressed = bnd.file_contents(&bnd.files[2]).expect("failed to read");
+            assert_eq!(uncompressed, &[0x2u8; 5][..]);
+
+            let view = Bnd4::parse(&bytes).expect("failed to parse BND4");
+            for index in 0..2 {
+                let file = view.file(index).expect("failed to parse BND4 entry");
+                assert!(file.is_compressed());
+                assert_eq!(file.uncompressed_size, contents().len() as u64);
+                assert_eq!(file.contents().expect("failed to decompress"), contents());
+            }
+
+            let file = view.file(2).expect("failed to parse BND4 entry");
+            assert!(!file.is_compressed());
+            assert_eq!(file.contents().expect("failed to read"), &[0x2u8; 5][..]);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_magic() {
+        let mut bytes = compressed_archive(false);
+        bytes[..4].copy_from_slice(b"BND3");
+
+        let error = BND4::from_reader(Cursor::new(&bytes)).expect_err("read a BND3 as BND4");
+        assert!(matches!(error, Bnd4Error::InvalidMagic));
+
+        let error = Bnd4::parse(&bytes).expect_err("parsed a BND3 as BND4");
+        assert!(matches!(error, Bnd4Error::InvalidMagic));
+    }
+
+    #[test]
+    fn rejects_wrong_uncompressed_sizes() {
+        let bytes = compressed_archive(false);
+        let bnd = BND4::from_reader(Cursor::new(&bytes)).expect("failed to read BND4");
+
+        let mut builder = BND4Builder::from_bnd4(&bnd);
+        builder.files[0].uncompressed_size -= 1;
+        builder.files[1].uncompressed_size = 0xFFFF_FFFF_FFFF;
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        let bnd = BND4::from_reader(Cursor::new(&written)).expect("failed to read BND4");
+        for file in &bnd.files[..2] {
+            let error = bnd
+                .file_contents(file)
+                .expect_err("decompressed to the wrong size");
+            assert!(matches!(
+                error,
+                Bnd4Error::Dcx(DcxError::UncompressedSizeMismatch { actual: 0x1000, .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use byteorder::{BE, LE};
+use zerocopy::{ByteOrder, FromBytes, FromZeroes, Ref, I32, U32, U64};
+
+use crate::{bnd4::path_hash, io_ext::zerocopy::Padding};
+
+/// Indices of the entries whose path has the same hash as `path`, looked up in the hash bucket
+/// table of an archive. Returns `None` if the table is missing or malformed, in which case
+/// entries need to be searched by name instead.
+pub(crate) fn candidates(
+    bytes: &[u8],
+    big_endian: bool,
+    buckets_offset: u64,
+    path: &str,
+) -> Option<Vec<usize>> {
+    if buckets_offset == 0 {
+        return None;
+    }
+
+    if big_endian {
+        candidates_with::<BE>(bytes, buckets_offset, path)
+    } else {
+        candidates_with::<LE>(bytes, buckets_offset, path)
+    }
+}
+
+fn candidates_with<O: ByteOrder>(
+    bytes: &[u8],
+    buckets_offset: u64,
+    path: &str,
+) -> Option<Vec<usize>> {
+    let table = bytes.get(usize::try_from(buckets_offset).ok()?..)?;
+    let (header, table) = Ref::<_, Header<O>>::new_from_prefix(table)?;
+    let bucket_count = header.bucket_count.get();
+    if bucket_count == 0 {
+        return None;
+    }
+
+    let (buckets, _) = Bucket::<O>::slice_from_prefix(table, bucket_count as usize)?;
+    let hashes = bytes.get(usize::try_from(header.hashes_offset.get()).ok()?..)?;
+
+    let hash = path_hash(path);
+    let bucket = &buckets[(hash % bucket_count) as usize];
+    let first = usize::try_from(bucket.index.get()).ok()?;
+    let count = usize::try_from(bucket.count.get()).ok()?;
+
+    let (hashes, _) = PathHash::<O>::slice_from_prefix(hashes, first.checked_add(count)?)?;
+
+    Some(
+        hashes[first..]
+            .iter()
+            .filter(|entry| entry.hash.get() == hash)
+            .filter_map(|entry| usize::try_from(entry.index.get()).ok())
+            .collect(),
+    )
+}
+
+#[derive(FromZeroes, FromBytes)]
+#[repr(C)]
+#[allow(unused)]
+struct Header<O: ByteOrder> {
+    hashes_offset: U64<O>,
+    bucket_count: U32<O>,
+
+    /// Sizes of the table header and its entries, always `10 08 08 00`.
+    _sizes: Padding<4>,
+}
+
+#[derive(FromZeroes, FromBytes)]
+#[repr(C)]
+struct Bucket<O: ByteOrder> {
+    count: I32<O>,
+    index: I32<O>,
+}
+
+#[derive(FromZeroes, FromBytes)]
+#[repr(C)]
+struct PathHash<O: ByteOrder> {
+    hash: U32<O>,
+    index: I32<O>,
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::bnd4::{BND4Builder, Bnd4, BND4};
+
+    fn archive(extended: u8) -> Vec<u8> {
+        let mut builder = BND4Builder::new();
+        builder.extended = extended;
+        builder
+            .add_file(0, "N:\\GR\\data\\c0000.flver", vec![0x1u8; 37])
+            .add_file(1, "N:\\GR\\data\\c0000.anibnd", Vec::new())
+            .add_file(2, "N:\\GR\\data\\c0000.tpf", vec![0x2u8; 5]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        written
+    }
+
+    #[test]
+    fn finds_entries() {
+        // Without a bucket table entries are looked up by comparing their names
+        for extended in [4, 0] {
+            let bytes = archive(extended);
+            let bnd = BND4::from_reader(Cursor::new(&bytes)).expect("failed to read BND4");
+            let view = Bnd4::parse(&bytes).expect("failed to parse BND4");
+
+            let by_path = bnd.file_by_path("n:/gr/data/c0000.anibnd");
+            assert_eq!(by_path.map(|file| file.id), Some(1));
+            assert!(bnd.file_by_path("n:/gr/data/c0001.anibnd").is_none());
+            let by_id = bnd.file_by_id(2).map(|file| file.path.as_str());
+            assert_eq!(by_id, Some("N:\\GR\\data\\c0000.tpf"));
+            assert_eq!(bnd.files_by_extension("tpf").count(), 1);
+
+            let by_path = view.file_by_path("N:\\GR\\data\\C0000.TPF");
+            assert_eq!(by_path.ok().flatten().map(|file| file.id), Some(2));
+            assert_eq!(view.files_by_extension(".FLVER").count(), 1);
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use std::{borrow::Cow, mem::size_of};
+
+use byteorder::{BE, LE};
+use encoding_rs::SHIFT_JIS;
+use utf16string::WStr;
+use zerocopy::{ByteOrder, FromBytes, FromZeroes, Ref, I32, U32, U64};
+
+use crate::{
+    bnd4::{
+        buckets, decompress_entry, extension_suffix, hashable_path, BND4Entry, BinderFormat,
+        Bnd4Error,
+    },
+    io_ext::zerocopy::Padding,
+};
+
+/// A BND4 archive borrowed from a byte slice, e.g. a decompressed DCX buffer or the memory map
+/// behind a `DvdBndEntryReader`. Entry headers are parsed on access and entry data is handed out as
+/// slices of the input.
+#[derive(Clone, Debug)]
+pub struct Bnd4<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+    bit_big_endian: bool,
+    unicode: bool,
+    format: BinderFormat,
+    extended: u8,
+    version: u64,
+    file_count: usize,
+    file_headers_offset: u64,
+    file_header_size: usize,
+    buckets_offset: u64,
+}
+
+impl<'a> Bnd4<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Bnd4Error> {
+        if !bytes.starts_with(b"BND4") {
+            return Err(Bnd4Error::InvalidMagic);
+        }
+
+        match bytes.get(0x9) {
+            Some(0x0) => Self::parse_with::<LE>(bytes, false),
+            Some(_) => Self::parse_with::<BE>(bytes, true),
+            None => Err(Bnd4Error::Truncated),
+        }
+    }
+
+    fn parse_with<O: ByteOrder>(bytes: &'a [u8], big_endian: bool) -> Result<Self, Bnd4Error> {
+        let (header, _) =
+            Ref::<_, Header<O>>::new_from_prefix(bytes).ok_or(Bnd4Error::Truncated)?;
+
+        let bit_big_endian = header.unk0a == 0;
+        let format = BinderFormat::from_raw(header.raw_format, bit_big_endian);
+        let file_header_size = header.file_header_size.get();
+
+        // Entry headers might be followed by fields this parser doesn't know of, but can't be
+        // smaller than the fields it reads.
+        if file_header_size < format.bnd4_entry_header_size() {
+            return Err(Bnd4Error::InvalidHeaderSize(file_header_size));
+        }
+
+        Ok(Self {
+            bytes,
+            big_endian,
+            bit_big_endian,
+            unicode: header.unicode == 0x1,
+            format,
+            extended: header.extended,
+            version: header.version.get(),
+            file_count: header.file_count.get() as usize,
+            file_headers_offset: header.file_headers_offset.get(),
+            file_header_size: usize::try_from(file_header_size)
+                .map_err(|_| Bnd4Error::InvalidHeaderSize(file_header_size))?,
+            buckets_offset: header.buckets_offset.get(),
+        })
+    }
+
+    /// The bytes this archive was parsed from.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    pub fn unicode(&self) -> bool {
+        self.unicode
+    }
+
+    pub fn format(&self) -> BinderFormat {
+        self.format
+    }
+
+    pub fn extended(&self) -> u8 {
+        self.extended
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.file_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.file_count == 0
+    }
+
+    /// The entry at `index`, which must be less than [`Bnd4::len`].
+    pub fn file(&self, index: usize) -> Result<Bnd4File<'a>, Bnd4Error> {
+        if index >= self.file_count {
+            return Err(Bnd4Error::IndexOutOfRange {
+                index,
+                len: self.file_count,
+            });
+        }
+
+        if self.big_endian {
+            self.file_with::<BE>(index)
+        } else {
+            self.file_with::<LE>(index)
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = Result<Bnd4File<'a>, Bnd4Error>> + '_ {
+        (0..self.file_count).map(|index| self.file(index))
+    }
+
+    pub fn file_by_id(&self, id: u32) -> Result<Option<Bnd4File<'a>>, Bnd4Error> {
+        for file in self.files() {
+            let file = file?;
+            if file.id == id {
+                return Ok(Some(file));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Look up an entry by its full path, see [`BND4::file_by_path`].
+    ///
+    /// [`BND4::file_by_path`]: crate::bnd4::BND4::file_by_path
+    pub fn file_by_path(&self, path: &str) -> Result<Option<Bnd4File<'a>>, Bnd4Error> {
+        let path = hashable_path(path);
+        let buckets_offset = if self.extended == 4 {
+            self.buckets_offset
+        } else {
+            0
+        };
+
+        match buckets::candidates(self.bytes, self.big_endian, buckets_offset, &path) {
+            Some(candidates) => self.find_path(candidates.into_iter(), &path),
+            None => self.find_path(0..self.file_count, &path),
+        }
+    }
+
+    fn find_path<I: Iterator<Item = usize>>(
+        &self,
+        indices: I,
+        path: &str,
+    ) -> Result<Option<Bnd4File<'a>>, Bnd4Error> {
+        for index in indices.filter(|index| *index < self.file_count) {
+            let file = self.file(index)?;
+            if hashable_path(&file.name()?) == path {
+                return Ok(Some(file));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Entries with a path ending in `extension`, e.g. `flver` or `.anibnd.dcx`, ignoring case.
+    pub fn files_by_extension(
+        &self,
+        extension: &str,
+    ) -> impl Iterator<Item = Result<Bnd4File<'a>, Bnd4Error>> + '_ {
+        let suffix = extension_suffix(extension);
+
+        self.files().filter(move |file| match file {
+            Ok(file) => file
+                .name()
+                .is_ok_and(|name| name.to_lowercase().ends_with(&suffix)),
+            Err(_) => true,
+        })
+    }
+
+    fn file_with<O: ByteOrder>(&self, index: usize) -> Result<Bnd4File<'a>, Bnd4Error> {
+        // Offsets that don't fit in a usize can't be within the input either.
+        let start = usize::try_from(self.file_headers_offset)
+            .ok()
+            .zip(index.checked_mul(self.file_header_size))
+            .and_then(|(headers, offset)| headers.checked_add(offset))
+            .ok_or(Bnd4Error::Truncated)?;
+
+        let mut fields = FieldReader(self.bytes.get(start..).ok_or(Bnd4Error::Truncated)?);
+        let format = self.format;
+
+        let flags = fields.read::<u8>()?;
+        fields.read::<Padding<3>>()?;
+        fields.read::<I32<O>>()?;
+
+        let compressed_size = fields.read::<U64<O>>()?.get();
+        let uncompressed_size = if format.has_compression() {
+            fields.read::<U64<O>>()?.get()
+        } else {
+            compressed_size
+        };
+
+        let data_offset = if format.has_long_offsets() {
+            fields.read::<U64<O>>()?.get()
+        } else {
+            fields.read::<U32<O>>()?.get() as u64
+        };
+
+        let mut id = if format.has_ids() {
+            fields.read::<U32<O>>()?.get()
+        } else {
+            u32::MAX
+        };
+
+        let name = if format.has_names() {
+            let name_offset = fields.read::<U32<O>>()?.get() as usize;
+            let name_bytes = self
+                .bytes
+                .get(name_offset..)
+                .ok_or(Bnd4Error::InvalidName)?;
+
+            let length = if self.unicode {
+                name_bytes
+                    .chunks_exact(2)
+                    .position(|unit| unit == [0x0, 0x0])
+                    .map(|units| units * 2)
+            } else {
+                name_bytes.iter().position(|b| *b == 0x0)
+            };
+
+            &name_bytes[..length.ok_or(Bnd4Error::InvalidName)?]
+        } else {
+            &[]
+        };
+
+        if format.has_trailing_id() {
+            id = fields.read::<U32<O>>()?.get();
+        }
+
+        let data = data_offset
+            .checked_add(compressed_size)
+            .and_then(|end| {
+                let start = usize::try_from(data_offset).ok()?;
+                let end = usize::try_from(end).ok()?;
+
+                self.bytes.get(start..end)
+            })
+            .ok_or(Bnd4Error::EntryOutOfBounds {
+                offset: data_offset,
+                size: compressed_size,
+            })?;
+
+        let compressed =
+            self.format.entry_flags(flags, self.bit_big_endian) & BND4Entry::FLAG_COMPRESSED != 0;
+
+        Ok(Bnd4File {
+            flags,
+            id,
+            data_offset,
+            uncompressed_size,
+            compressed,
+            name,
+            name_encoding: match (self.unicode, self.big_endian) {
+                (false, _) => NameEncoding::ShiftJis,
+                (true, false) => NameEncoding::Utf16Le,
+                (true, true) => NameEncoding::Utf16Be,
+            },
+            data,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NameEncoding {
+    ShiftJis,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// An entry of a borrowed [`Bnd4`] archive.
+#[derive(Clone, Debug)]
+pub struct Bnd4File<'a> {
+    /// Entry flags as stored in the archive, see [`BinderFormat::entry_flags`].
+    pub flags: u8,
+    pub id: u32,
+
+    /// Offset of the entry data from the start of the archive.
+    pub data_offset: u64,
+    pub uncompressed_size: u64,
+    compressed: bool,
+
+    /// Encoded name without its terminator.
+    name: &'a [u8],
+    name_encoding: NameEncoding,
+    data: &'a [u8],
+}
+
+impl<'a> Bnd4File<'a> {
+    /// Path of the entry, which is empty for archives without names. Only allocates when the name
+    /// is stored as UTF-16 or isn't plain ASCII.
+    pub fn name(&self) -> Result<Cow<'a, str>, Bnd4Error> {
+        let name = match self.name_encoding {
+            NameEncoding::ShiftJis => {
+                let (name, had_errors) = SHIFT_JIS.decode_without_bom_handling(self.name);
+                if had_errors {
+                    return Err(Bnd4Error::InvalidName);
+                }
+
+                name
+            }
+            NameEncoding::Utf16Le => Cow::Owned(
+                WStr::<LE>::from_utf16(self.name)
+                    .map_err(|_| Bnd4Error::InvalidName)?
+                    .to_utf8(),
+            ),
+            NameEncoding::Utf16Be => Cow::Owned(
+                WStr::<BE>::from_utf16(self.name)
+                    .map_err(|_| Bnd4Error::InvalidName)?
+                    .to_utf8(),
+            ),
+        };
+
+        Ok(name)
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// The entry data as stored in the archive, which is not decompressed.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Contents of the entry, only copied when it has to be decompressed.
+    pub fn contents(&self) -> Result<Cow<'a, [u8]>, Bnd4Error> {
+        if self.compressed {
+            Ok(Cow::Owned(decompress_entry(
+                self.data,
+                self.uncompressed_size,
+            )?))
+        } else {
+            Ok(Cow::Borrowed(self.data))
+        }
+    }
+}
+
+/// Reads consecutive fields of an entry header.
+struct FieldReader<'a>(&'a [u8]);
+
+impl<'a> FieldReader<'a> {
+    fn read<T: FromBytes>(&mut self) -> Result<T, Bnd4Error> {
+        let value = T::read_from_prefix(self.0).ok_or(Bnd4Error::Truncated)?;
+        self.0 = &self.0[size_of::<T>()..];
+
+        Ok(value)
+    }
+}
+
+#[derive(FromZeroes, FromBytes)]
+#[repr(C)]
+#[allow(unused)]
+struct Header<O: ByteOrder> {
+    magic: [u8; 4],
+    unk04: u8,
+    unk05: u8,
+    _pad0: Padding<3>,
+    big_endian: u8,
+    unk0a: u8,
+    _pad1: Padding<1>,
+    file_count: U32<O>,
+    file_headers_offset: U64<O>,
+    version: U64<O>,
+    file_header_size: U64<O>,
+    file_headers_end: U64<O>,
+    unicode: u8,
+    raw_format: u8,
+    extended: u8,
+    _pad2: Padding<5>,
+    buckets_offset: U64<O>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::Bnd4;
+    use crate::bnd4::{BND4Builder, Bnd4Error};
+
+    fn archive() -> Vec<u8> {
+        let mut builder = BND4Builder::new();
+        builder
+            .add_file(0, "N:\\GR\\data\\c0000.flver", vec![0x1u8; 37])
+            .add_file(1, "N:\\GR\\data\\c0000.tpf", vec![0x2u8; 5]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        written
+    }
+
+    #[test]
+    fn borrows_entries() {
+        let bytes = archive();
+        let view = Bnd4::parse(&bytes).expect("failed to parse BND4");
+        assert_eq!(view.len(), 2);
+
+        let file = view.file(1).expect("failed to parse BND4 entry");
+        assert_eq!(file.id, 1);
+        assert_eq!(
+            file.name().expect("invalid name"),
+            "N:\\GR\\data\\c0000.tpf"
+        );
+        assert_eq!(file.data(), &[0x2u8; 5][..]);
+        assert!(std::ptr::eq(
+            file.data().as_ptr(),
+            &bytes[file.data_offset as usize]
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let bytes = archive();
+        let view = Bnd4::parse(&bytes).expect("failed to parse BND4");
+
+        let error = view.file(2).expect_err("parsed entry past the end");
+        assert!(matches!(
+            error,
+            Bnd4Error::IndexOutOfRange { index: 2, len: 2 }
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        let bytes = archive();
+
+        let error = Bnd4::parse(&bytes[..0x20]).expect_err("parsed truncated header");
+        assert!(matches!(error, Bnd4Error::Truncated));
+
+        let mut small_headers = bytes.clone();
+        small_headers[0x20..0x28].copy_from_slice(&0x8u64.to_le_bytes());
+
+        let error = Bnd4::parse(&small_headers).expect_err("parsed undersized entry headers");
+        assert!(matches!(error, Bnd4Error::InvalidHeaderSize(0x8)));
+    }
+}
@@ -0,0 +1,518 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+
+use byteorder::{ByteOrder, WriteBytesExt, BE, LE};
+
+use crate::{
+    bnd4::{path_hash, BinderFormat, BND4},
+    io_ext::WriteFormatsExt,
+};
+
+const HEADER_SIZE: u64 = 0x40;
+const BUCKETS_ALIGNMENT: u64 = 0x8;
+const DATA_ALIGNMENT: u64 = 0x10;
+
+/// Entry flags used by the game for regular files, as stored in little-endian bit order.
+const DEFAULT_ENTRY_FLAGS: u8 = 0x40;
+
+/// A file that will be written to a BND4 archive by a [`BND4Builder`].
+#[derive(Clone, Debug)]
+pub struct BND4BuilderEntry<'a> {
+    pub flags: u8,
+    pub unk4: i32,
+    pub id: u32,
+    pub path: String,
+
+    /// Size of the entry contents once decompressed. Equal to the length of [`data`] for
+    /// uncompressed entries.
+    ///
+    /// [`data`]: BND4BuilderEntry::data
+    pub uncompressed_size: u64,
+
+    /// Entry contents as they will be stored in the archive.
+    pub data: Cow<'a, [u8]>,
+
+    /// Offset of the contents in the archive the entry was read from. The contents are written
+    /// back to the same offset when it's still free, which keeps the padding of unmodified
+    /// archives intact. Entries without an offset are appended after all other data.
+    pub data_offset: Option<u64>,
+}
+
+impl<'a> BND4BuilderEntry<'a> {
+    /// Replace the contents of this entry with uncompressed data.
+    pub fn set_data<D: Into<Cow<'a, [u8]>>>(&mut self, data: D) {
+        self.data = data.into();
+        self.uncompressed_size = self.data.len() as u64;
+        self.data_offset = None;
+    }
+}
+
+/// Lays out and serializes a BND4 archive. Header fields not covered here (offsets, sizes and the
+/// hash bucket table) are derived from the entries when writing.
+///
+/// A builder created with [`BND4Builder::from_bnd4`] writes the exact bytes of the archive it was
+/// created from, as long as its entries are left unmodified. Unmodified entries of an archive that
+/// was changed keep their offsets.
+#[derive(Clone, Debug)]
+pub struct BND4Builder<'a> {
+    pub unk04: u8,
+    pub unk05: u8,
+    pub big_endian: bool,
+
+    /// Zero when the format and entry flags are stored in big-endian bit order.
+    pub unk0a: u8,
+
+    /// Offset of the entry headers, which directly follow the 0x40 byte header unless the
+    /// archive was read from a file that pads it.
+    pub file_headers_offset: u64,
+    pub version: u64,
+    pub unicode: bool,
+    pub raw_format: u8,
+
+    /// When set to `4` a hash bucket table is written for entry lookups by name.
+    pub extended: u8,
+    pub files: Vec<BND4BuilderEntry<'a>>,
+}
+
+impl<'a> Default for BND4Builder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> BND4Builder<'a> {
+    /// Create an empty builder using the header values found in Elden Ring archives.
+    pub fn new() -> Self {
+        Self {
+            unk04: 0,
+            unk05: 0,
+            big_endian: false,
+            unk0a: 1,
+            file_headers_offset: HEADER_SIZE,
+            version: u64::from_le_bytes(*b"07D7R6\0\0"),
+            unicode: true,
+            raw_format: 0x74,
+            extended: 4,
+            files: Vec::new(),
+        }
+    }
+
+    /// Create a builder from the header and entries of an existing archive, borrowing the entry
+    /// contents from it.
+    pub fn from_bnd4(bnd: &'a BND4) -> Self {
+        Self {
+            unk04: bnd.unk04,
+            unk05: bnd.unk05,
+            big_endian: bnd.big_endian,
+            unk0a: bnd.unk0a,
+            file_headers_offset: bnd.file_headers_offset,
+            version: bnd.version,
+            unicode: bnd.unicode,
+            raw_format: bnd.raw_format,
+            extended: bnd.extended,
+            files: bnd
+                .files
+                .iter()
+                .map(|file| BND4BuilderEntry {
+                    flags: file.flags,
+                    unk4: file.unk4,
+                    id: file.id,
+                    path: file.path.clone(),
+                    uncompressed_size: file.uncompressed_size,
+                    data: Cow::Borrowed(bnd.file_bytes(file)),
+                    data_offset: Some(file.data_offset),
+                })
+                .collect(),
+        }
+    }
+
+    /// Append an uncompressed file to the archive.
+    pub fn add_file<P: Into<String>, D: Into<Cow<'a, [u8]>>>(
+        &mut self,
+        id: u32,
+        path: P,
+        data: D,
+    ) -> &mut Self {
+        let data = data.into();
+
+        self.files.push(BND4BuilderEntry {
+            flags: DEFAULT_ENTRY_FLAGS,
+            unk4: -1,
+            id,
+            path: path.into(),
+            uncompressed_size: data.len() as u64,
+            data,
+            data_offset: None,
+        });
+
+        self
+    }
+
+    pub fn format(&self) -> BinderFormat {
+        BinderFormat::from_raw(self.raw_format, self.unk0a == 0)
+    }
+
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        if self.big_endian {
+            self.write_with::<BE, W>(w)
+        } else {
+            self.write_with::<LE, W>(w)
+        }
+    }
+
+    fn write_with<BO: ByteOrder, W: Write>(&self, mut w: W) -> io::Result<()> {
+        let format = self.format();
+        let entry_header_size = format.bnd4_entry_header_size();
+        if self.file_headers_offset < HEADER_SIZE {
+            return Err(io::Error::other("entry headers overlap the archive header"));
+        }
+
+        let names_offset = self.file_headers_offset + entry_header_size * self.files.len() as u64;
+
+        let mut names = Vec::new();
+        let mut name_offsets = Vec::with_capacity(self.files.len());
+
+        if format.has_names() {
+            for file in &self.files {
+                name_offsets.push(names_offset + names.len() as u64);
+
+                if self.unicode {
+                    names.write_utf16::<BO>(&file.path)?;
+                } else {
+                    names.write_shift_jis(&file.path)?;
+                }
+            }
+        }
+
+        let names_end = names_offset + names.len() as u64;
+        let (buckets_offset, buckets) = if self.extended == 4 {
+            let buckets_offset = names_end.next_multiple_of(BUCKETS_ALIGNMENT);
+            let mut buckets = Vec::new();
+            self.write_buckets::<BO>(&mut buckets, buckets_offset)?;
+
+            (buckets_offset, buckets)
+        } else {
+            (0, Vec::new())
+        };
+
+        let file_headers_end = if buckets.is_empty() {
+            names_end
+        } else {
+            buckets_offset + buckets.len() as u64
+        };
+
+        let data_offsets = self.data_offsets(file_headers_end);
+
+        w.write_all(b"BND4")?;
+        w.write_u8(self.unk04)?;
+        w.write_u8(self.unk05)?;
+        w.write_padding(3)?;
+        w.write_u8(self.big_endian as u8)?;
+        w.write_u8(self.unk0a)?;
+        w.write_padding(1)?;
+        w.write_u32::<BO>(self.files.len() as u32)?;
+        w.write_u64::<BO>(self.file_headers_offset)?;
+        w.write_u64::<BO>(self.version)?;
+        w.write_u64::<BO>(entry_header_size)?;
+        w.write_u64::<BO>(file_headers_end)?;
+        w.write_u8(self.unicode as u8)?;
+        w.write_u8(self.raw_format)?;
+        w.write_u8(self.extended)?;
+        w.write_padding(5)?;
+        w.write_u64::<BO>(buckets_offset)?;
+        w.write_padding((self.file_headers_offset - HEADER_SIZE) as usize)?;
+
+        for (index, file) in self.files.iter().enumerate() {
+            w.write_u8(file.flags)?;
+            w.write_padding(3)?;
+            w.write_i32::<BO>(file.unk4)?;
+            w.write_u64::<BO>(file.data.len() as u64)?;
+
+            if format.has_compression() {
+                w.write_u64::<BO>(file.uncompressed_size)?;
+            }
+
+            if format.has_long_offsets() {
+                w.write_u64::<BO>(data_offsets[index])?;
+            } else {
+                let offset = u32::try_from(data_offsets[index]).map_err(|_| {
+                    io::Error::other("archive is too large for 32-bit data offsets")
+                })?;
+
+                w.write_u32::<BO>(offset)?;
+            }
+
+            if format.has_ids() {
+                w.write_u32::<BO>(file.id)?;
+            }
+
+            if format.has_names() {
+                let offset = u32::try_from(name_offsets[index])
+                    .map_err(|_| io::Error::other("entry names don't fit 32-bit offsets"))?;
+
+                w.write_u32::<BO>(offset)?;
+            }
+
+            if format.has_trailing_id() {
+                w.write_u32::<BO>(file.id)?;
+                w.write_padding(4)?;
+            }
+        }
+
+        w.write_all(&names)?;
+
+        if !buckets.is_empty() {
+            w.write_padding((buckets_offset - names_end) as usize)?;
+            w.write_all(&buckets)?;
+        }
+
+        let mut files: Vec<_> = self
+            .files
+            .iter()
+            .zip(data_offsets)
+            .filter(|(file, _)| !file.data.is_empty())
+            .collect();
+        files.sort_unstable_by_key(|(_, offset)| *offset);
+
+        let mut position = file_headers_end;
+        for (file, offset) in files {
+            w.write_padding((offset - position) as usize)?;
+            w.write_all(&file.data)?;
+
+            position = offset + file.data.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Offsets the entry contents are written to. Entries keep their original offset unless it
+    /// overlaps the headers or the contents of another entry, the remaining entries are appended
+    /// in order after all other data. Empty entries always keep their offset.
+    fn data_offsets(&self, file_headers_end: u64) -> Vec<u64> {
+        let mut offsets: Vec<_> = self
+            .files
+            .iter()
+            .map(|file| file.data_offset.filter(|_| file.data.is_empty()))
+            .collect();
+
+        let mut kept: Vec<_> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| !file.data.is_empty())
+            .filter_map(|(index, file)| Some((file.data_offset?, index)))
+            .filter(|(offset, _)| *offset >= file_headers_end)
+            .collect();
+        kept.sort_unstable();
+
+        let mut position = file_headers_end;
+        for (offset, index) in kept {
+            if offset >= position {
+                offsets[index] = Some(offset);
+                position = offset + self.files[index].data.len() as u64;
+            }
+        }
+
+        self.files
+            .iter()
+            .zip(offsets)
+            .map(|(file, offset)| {
+                offset.unwrap_or_else(|| {
+                    if !file.data.is_empty() {
+                        position = position.next_multiple_of(DATA_ALIGNMENT);
+                    }
+
+                    let offset = position;
+                    position += file.data.len() as u64;
+
+                    offset
+                })
+            })
+            .collect()
+    }
+
+    /// Write the hash bucket table used by the game to look up entries by name. Entries are
+    /// distributed over a prime number of buckets and sorted by hash within each bucket.
+    fn write_buckets<BO: ByteOrder>(&self, w: &mut Vec<u8>, buckets_offset: u64) -> io::Result<()> {
+        let bucket_count = ((self.files.len() / 7) as u32..)
+            .find(|count| is_prime(*count))
+            .ok_or(io::Error::other("no bucket count for this many files"))?;
+
+        let mut buckets = vec![Vec::new(); bucket_count as usize];
+        for (index, file) in self.files.iter().enumerate() {
+            let hash = path_hash(&file.path);
+            buckets[(hash % bucket_count) as usize].push((hash, index as u32));
+        }
+
+        let hashes_offset = buckets_offset + 0x10 + 0x8 * bucket_count as u64;
+
+        w.write_u64::<BO>(hashes_offset)?;
+        w.write_u32::<BO>(bucket_count)?;
+        w.write_u8(0x10)?;
+        w.write_u8(0x8)?;
+        w.write_u8(0x8)?;
+        w.write_u8(0x0)?;
+
+        let mut first_index = 0;
+        for bucket in &mut buckets {
+            bucket.sort_by_key(|(hash, _)| *hash);
+
+            w.write_u32::<BO>(bucket.len() as u32)?;
+            w.write_u32::<BO>(first_index)?;
+
+            first_index += bucket.len() as u32;
+        }
+
+        for (hash, index) in buckets.iter().flatten() {
+            w.write_u32::<BO>(*hash)?;
+            w.write_u32::<BO>(*index)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl BND4 {
+    /// Serialize this archive. Unless entries were modified the output is identical to the bytes
+    /// it was read from.
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        BND4Builder::from_bnd4(self).write(w)
+    }
+}
+
+pub(crate) fn is_prime(value: u32) -> bool {
+    value >= 2
+        && (2..)
+            .take_while(|d| d * d <= value)
+            .all(|d| !value.is_multiple_of(d))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{BND4Builder, HEADER_SIZE};
+    use crate::bnd4::{Bnd4Error, BND4};
+
+    /// Offset of the data offset field in the entry headers written by [`BND4Builder::new`].
+    const DATA_OFFSET_FIELD: usize = 0x18;
+
+    /// An archive laid out like the games do rather than like the builder does, with the entry
+    /// contents aligned to 0x80 bytes and stored in reverse order.
+    fn padded_archive() -> Vec<u8> {
+        let mut builder = BND4Builder::new();
+        builder
+            .add_file(0, "N:\\GR\\data\\c0000.flver", vec![0x1u8; 37])
+            .add_file(1, "N:\\GR\\data\\c0000.anibnd", Vec::new())
+            .add_file(2, "N:\\GR\\data\\c0000.tpf", vec![0x2u8; 5]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        let bnd = BND4::from_reader(Cursor::new(&written)).expect("failed to read BND4");
+        let mut padded = written[..bnd.file_headers_end as usize].to_vec();
+
+        for (index, file) in bnd.files.iter().enumerate().rev() {
+            padded.resize(padded.len().next_multiple_of(0x80), 0x0);
+
+            let field = HEADER_SIZE as usize + index * bnd.file_header_size as usize;
+            let field = field + DATA_OFFSET_FIELD;
+            let offset = padded.len() as u32;
+            padded[field..field + 4].copy_from_slice(&offset.to_le_bytes());
+            padded.extend_from_slice(bnd.file_bytes(file));
+        }
+
+        padded
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut builder = BND4Builder::new();
+        builder
+            .add_file(0, "N:\\GR\\data\\c0000.flver", vec![0x1u8; 37])
+            .add_file(1, "N:\\GR\\data\\c0000.anibnd", Vec::new())
+            .add_file(2, "N:\\GR\\data\\c0000.tpf", vec![0x2u8; 5]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        let bnd = BND4::from_reader(Cursor::new(&written)).expect("failed to read BND4");
+        assert_eq!(bnd.files.len(), 3);
+        assert_eq!(bnd.files[2].path, "N:\\GR\\data\\c0000.tpf");
+        assert_eq!(bnd.file_bytes(&bnd.files[0]), &[0x1u8; 37][..]);
+        assert_eq!(bnd.file_bytes(&bnd.files[2]), &[0x2u8; 5][..]);
+
+        let mut rewritten = Vec::new();
+        bnd.write(&mut rewritten).expect("failed to rewrite BND4");
+
+        assert_eq!(written, rewritten);
+    }
+
+    #[test]
+    fn round_trips_big_endian() {
+        let mut builder = BND4Builder::new();
+        builder.big_endian = true;
+        builder
+            .add_file(0, "N:\\GR\\data\\c0000.flver", vec![0x1u8; 37])
+            .add_file(1, "N:\\GR\\data\\c0000.tpf", vec![0x2u8; 5]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+
+        let bnd = BND4::from_reader(Cursor::new(&written)).expect("failed to read BND4");
+        assert!(bnd.big_endian);
+        assert_eq!(bnd.files[1].path, "N:\\GR\\data\\c0000.tpf");
+        assert_eq!(bnd.file_bytes(&bnd.files[0]), &[0x1u8; 37][..]);
+
+        let mut rewritten = Vec::new();
+        bnd.write(&mut rewritten).expect("failed to rewrite BND4");
+
+        assert_eq!(written, rewritten);
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        let mut builder = BND4Builder::new();
+        builder.add_file(0, "N:\\GR\\data\\c0000.flver", vec![0x1u8; 37]);
+
+        let mut written = Vec::new();
+        builder.write(&mut written).expect("failed to write BND4");
+        written.truncate(written.len() - 1);
+
+        let result = BND4::from_reader(Cursor::new(&written));
+        assert!(matches!(result, Err(Bnd4Error::EntryOutOfBounds { .. })));
+    }
+
+    #[test]
+    fn rewrites_padded_archives() {
+        let padded = padded_archive();
+        let bnd = BND4::from_reader(Cursor::new(&padded)).expect("failed to read BND4");
+
+        let mut rewritten = Vec::new();
+        bnd.write(&mut rewritten).expect("failed to rewrite BND4");
+
+        assert_eq!(padded, rewritten);
+    }
+
+    #[test]
+    fn keeps_offsets_of_unmodified_entries() {
+        let padded = padded_archive();
+        let bnd = BND4::from_reader(Cursor::new(&padded)).expect("failed to read BND4");
+
+        let mut builder = BND4Builder::from_bnd4(&bnd);
+        builder.files[2].set_data(vec![0x3u8; 300]);
+
+        let mut rewritten = Vec::new();
+        builder
+            .write(&mut rewritten)
+            .expect("failed to rewrite BND4");
+
+        let modified = BND4::from_reader(Cursor::new(&rewritten)).expect("failed to read BND4");
+        assert_eq!(modified.files[0].data_offset, bnd.files[0].data_offset);
+        assert!(modified.files[2].data_offset >= padded.len() as u64);
+        assert_eq!(modified.file_bytes(&modified.files[0]), &[0x1u8; 37][..]);
+        assert_eq!(modified.file_bytes(&modified.files[2]), &[0x3u8; 300][..]);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    borrow::Cow,
+    io::{Read, Seek},
+};
+
+use crate::bnd4::{
+    buckets, check_entry_bounds, check_magic, decompress_entry, extension_suffix, hashable_path,
+    BND4Entry, Binder4Header, BinderFormat, Bnd4Error,
+};
+
+/// A split binder, stored as a BHF4 header holding the entry headers and names, and a BDF4 file
+/// holding the entry data. The game uses these for archives that are too large to load in one go,
+/// like the map textures in `.tpfbhd`/`.tpfbdt` pairs.
+///
+/// Both halves are separate files in the game archives, so they're read independently. Entries
+/// follow the same model as [`BND4`](crate::bnd4::BND4), except that data offsets point into the
+/// BDF4 file.
+#[derive(Debug)]
+pub struct BXF4 {
+    pub unk04: u8,
+    pub unk05: u8,
+
+    /// Whether the archive was written for a big-endian platform (PS3 and Xbox 360).
+    pub big_endian: bool,
+
+    /// Zero when the format and entry flags are stored in big-endian bit order.
+    pub unk0a: u8,
+    pub file_count: u32,
+    pub file_headers_offset: u64,
+    pub version: u64,
+    pub file_header_size: u64,
+    pub unicode: bool,
+    pub raw_format: u8,
+    pub extended: u8,
+    pub buckets_offset: u64,
+    pub files: Vec<BND4Entry>,
+
+    /// The BHF4 header file, which also holds the entry names and the hash bucket table.
+    pub header: Vec<u8>,
+
+    /// The BDF4 data file.
+    pub data: Vec<u8>,
+}
+
+impl BXF4 {
+    /// Read a split archive from its BHF4 header and BDF4 data files, e.g. the readers returned by
+    /// two separate `DvdBnd::open` calls.
+    pub fn from_readers<H: Read + Seek, D: Read>(
+        mut header: H,
+        mut data: D,
+    ) -> Result<Self, Bnd4Error> {
+        let (fields, files) = Binder4Header::read_with_entries(&mut header, b"BHF4")?;
+
+        let mut header_bytes = vec![];
+        header.rewind()?;
+        header.read_to_end(&mut header_bytes)?;
+
+        // The data file only repeats the header fields, entry offsets are relative to its start.
+        let mut data_bytes = vec![];
+        check_magic(&mut data, b"BDF4")?;
+        data_bytes.extend_from_slice(b"BDF4");
+        data.read_to_end(&mut data_bytes)?;
+
+        check_entry_bounds(&files, &data_bytes)?;
+
+        Ok(Self {
+            unk04: fields.unk04,
+            unk05: fields.unk05,
+            big_endian: fields.big_endian,
+            unk0a: fields.unk0a,
+            file_count: fields.file_count,
+            file_headers_offset: fields.file_headers_offset,
+            version: fields.version,
+            file_header_size: fields.file_header_size,
+            unicode: fields.unicode,
+            raw_format: fields.raw_format,
+            extended: fields.extended,
+            buckets_offset: fields.buckets_offset,
+            files,
+            header: header_bytes,
+            data: data_bytes,
+        })
+    }
+
+    /// The layout flags of this archive, decoded from [`BXF4::raw_format`].
+    pub fn format(&self) -> BinderFormat {
+        BinderFormat::from_raw(self.raw_format, self.unk0a == 0)
+    }
+
+    /// The entry data as stored in the data file. `handle` must be one of this archive's entries.
+    pub fn file_bytes(&self, handle: &BND4Entry) -> &[u8] {
+        let start = handle.data_offset as usize;
+        let end = start + handle.compressed_size as usize;
+
+        &self.data[start..end]
+    }
+
+    /// Contents of an entry, decompressed first if the archive stores it compressed.
+    pub fn file_contents(&self, handle: &BND4Entry) -> Result<Cow<'_, [u8]>, Bnd4Error> {
+        let bytes = self.file_bytes(handle);
+        if !handle.is_compressed() {
+            return Ok(Cow::Borrowed(bytes));
+        }
+
+        Ok(Cow::Owned(decompress_entry(
+            bytes,
+            handle.uncompressed_size,
+        )?))
+    }
+
+    pub fn file_by_id(&self, id: u32) -> Option<&BND4Entry> {
+        self.files.iter().find(|file| file.id == id)
+    }
+
+    /// Look up an entry by its full path, see [`BND4::file_by_path`].
+    ///
+    /// [`BND4::file_by_path`]: crate::bnd4::BND4::file_by_path
+    pub fn file_by_path(&self, path: &str) -> Option<&BND4Entry> {
+        let path = hashable_path(path);
+        let matches = |file: &&BND4Entry| hashable_path(&file.path) == path;
+
+        let buckets_offset = if self.extended == 4 {
+            self.buckets_offset
+        } else {
+            0
+        };
+
+        match buckets::candidates(&self.header, self.big_endian, buckets_offset, &path) {
+            Some(candidates) => candidates
+                .into_iter()
+                .filter_map(|index| self.files.get(index))
+                .find(matches),
+            None => self.files.iter().find(matches),
+        }
+    }
+
+    /// Entries with a path ending in `extension`, e.g. `tpf` or `.tpf.dcx`, ignoring case.
+    pub fn files_by_extension<'s>(
+        &'s self,
+        extension: &str,
+    ) -> impl Iterator<Item = &'s BND4Entry> + 's {
+        let suffix = extension_suffix(extension);
+
+        self.files
+            .iter()
+            .filter(move |file| file.path.to_lowercase().ends_with(&suffix))
+    }
+}
+
+/// Path of the data file that belongs to a split archive header, e.g. `m10_0000.tpfbdt` for
+/// `m10_0000.tpfbhd`. Returns `None` if `path` doesn't name a header file.
+pub fn data_path(header_path: &str) -> Option<String> {
+    let stem = header_path.strip_suffix("bhd")?;
+
+    Some(format!("{stem}bdt"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{data_path, BXF4};
+    use crate::bnd4::{BND4Builder, Bnd4Error};
+
+    /// The BHF4 header and BDF4 data halves of a split archive. Both share the BND4 layout closely
+    /// enough to build them from a single archive.
+    fn split_archive() -> (Vec<u8>, Vec<u8>) {
+        let mut builder = BND4Builder::new();
+        builder
+            .add_file(0, "m10_00_00_00_0000.tpf.dcx", vec![0x1u8; 21])
+            .add_file(1, "m10_00_00_00_0001.tpf.dcx", vec![0x2u8; 3]);
+
+        let mut bnd = Vec::new();
+        builder.write(&mut bnd).expect("failed to write BND4");
+
+        let mut header = bnd.clone();
+        header[..4].copy_from_slice(b"BHF4");
+        let mut data = bnd;
+        data[..4].copy_from_slice(b"BDF4");
+
+        (header, data)
+    }
+
+    #[test]
+    fn reads_split_archive() {
+        let (header, data) = split_archive();
+        let bxf = BXF4::from_readers(Cursor::new(header), Cursor::new(data))
+            .expect("failed to read BXF4");
+
+        let file = bxf
+            .file_by_path("M10_00_00_00_0001.TPF.DCX")
+            .expect("entry not found");
+        assert_eq!(file.id, 1);
+        assert_eq!(bxf.file_bytes(file), &[0x2u8; 3][..]);
+        assert_eq!(bxf.files_by_extension("tpf.dcx").count(), 2);
+
+        assert_eq!(
+            data_path("/map/m10/m10_0000.tpfbhd").as_deref(),
+            Some("/map/m10/m10_0000.tpfbdt")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_magic() {
+        let (header, data) = split_archive();
+
+        let result = BXF4::from_readers(Cursor::new(&data), Cursor::new(&data));
+        assert!(matches!(result, Err(Bnd4Error::InvalidMagic)));
+
+        let result = BXF4::from_readers(Cursor::new(&header), Cursor::new(&header));
+        assert!(matches!(result, Err(Bnd4Error::InvalidMagic)));
+    }
+}
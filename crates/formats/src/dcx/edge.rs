@@ -0,0 +1,469 @@
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    sync::Arc,
+    vec,
+};
+
+use byteorder::BE;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use rayon::prelude::*;
+use zerocopy::{FromBytes, U32};
+
+use super::DcxError;
+
+const MAGIC_TABLE: &[u8; 4] = b"EgdT";
+
+/// Uncompressed size of each block, the only size used by the games.
+pub(crate) const BLOCK_SIZE: usize = 0x10000;
+
+/// Version and header size of the block table in the DCX layout. The DCP layout of Demon's Souls
+/// uses version `0x10000` and a header without the size of the last block.
+const TABLE_VERSION: u32 = 0x10100;
+const TABLE_HEADER_SIZE: usize = 0x24;
+const TABLE_ENTRY_SIZE: usize = 0x10;
+
+/// Number of decoded blocks kept by an [`EdgeBlockReader`].
+const CACHED_BLOCKS: usize = 16;
+
+struct EdgeTable {
+    /// Uncompressed size of every block but the last.
+    block_size: usize,
+    blocks: Vec<EdgeBlock>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct EdgeBlock {
+    /// Offset of the block from the start of the compressed data.
+    offset: u32,
+    size: u32,
+
+    /// Whether the block is raw deflate data, blocks that don't compress are stored as-is.
+    compressed: bool,
+}
+
+impl EdgeBlock {
+    fn range(&self) -> Range<usize> {
+        self.offset as usize..self.offset as usize + self.size as usize
+    }
+
+    /// Decode the block from its `contents` in the compressed data, appending it to `output`.
+    /// Blocks that decode to more than `block_size` bytes are rejected.
+    fn decode(&self, contents: &[u8], block_size: usize, output: &mut Vec<u8>) -> io::Result<()> {
+        let start = output.len();
+
+        if self.compressed {
+            DeflateDecoder::new(contents)
+                .take(block_size as u64 + 1)
+                .read_to_end(output)?;
+        } else {
+            output.extend_from_slice(contents);
+        }
+
+        if output.len() - start > block_size {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "EDGE block is larger than the block size",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+enum EdgeSource<R: Read> {
+    Stream(R),
+    Buffered(Arc<[u8]>),
+}
+
+/// Decodes the `EDGE` format used on the PS3, which splits the data into independently deflated
+/// blocks listed in a table stored in the DCA chunk.
+pub struct EdgeDecoder<R: Read> {
+    source: EdgeSource<R>,
+    block_size: usize,
+    blocks: vec::IntoIter<EdgeBlock>,
+
+    /// Offset into the compressed data that a streaming source is at.
+    position: u64,
+
+    /// Compressed contents of the current block, when read from a stream.
+    compressed: Vec<u8>,
+
+    /// Decoded contents of the current block.
+    block: Vec<u8>,
+    block_position: usize,
+}
+
+impl<R: Read> EdgeDecoder<R> {
+    /// Decode the data read from `reader` using the block table in `data_info`, the contents of the
+    /// DCA chunk.
+    pub fn new(reader: R, data_info: &[u8]) -> Result<Self, DcxError> {
+        Self::with_source(EdgeSource::Stream(reader), data_info)
+    }
+
+    /// Decode compressed data that was already read, e.g. because the block table follows it.
+    pub fn from_buffer(data: Arc<[u8]>, data_info: &[u8]) -> Result<Self, DcxError> {
+        Self::with_source(EdgeSource::Buffered(data), data_info)
+    }
+
+    fn with_source(source: EdgeSource<R>, data_info: &[u8]) -> Result<Self, DcxError> {
+        let table = read_table(data_info)?;
+
+        Ok(Self {
+            source,
+            block_size: table.block_size,
+            blocks: table.blocks.into_iter(),
+            position: 0,
+            compressed: Vec::new(),
+            block: Vec::with_capacity(BLOCK_SIZE),
+            block_position: 0,
+        })
+    }
+
+    fn decode_block(&mut self, block: EdgeBlock) -> io::Result<()> {
+        let offset = block.offset as u64;
+        let size = block.size as u64;
+
+        let compressed: &[u8] = match &mut self.source {
+            EdgeSource::Stream(reader) => {
+                if offset < self.position {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "EDGE blocks are not in order",
+                    ));
+                }
+
+                let padding = offset - self.position;
+                if io::copy(&mut reader.by_ref().take(padding), &mut io::sink())? != padding {
+                    return Err(ErrorKind::UnexpectedEof.into());
+                }
+
+                self.compressed.resize(block.size as usize, 0);
+                reader.read_exact(&mut self.compressed)?;
+                self.position = offset + size;
+
+                &self.compressed
+            }
+            EdgeSource::Buffered(data) => {
+                data.get(block.range()).ok_or(ErrorKind::UnexpectedEof)?
+            }
+        };
+
+        self.block.clear();
+        self.block_position = 0;
+
+        block.decode(compressed, self.block_size, &mut self.block)
+    }
+}
+
+impl<R: Read> Read for EdgeDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.block_position == self.block.len() {
+            let Some(block) = self.blocks.next() else {
+                return Ok(0);
+            };
+
+            self.decode_block(block)?;
+        }
+
+        let read = (&self.block[self.block_position..]).read(buf)?;
+        self.block_position += read;
+
+        Ok(read)
+    }
+}
+
+/// Parse the block table from the contents of the DCA chunk. The header size and entry size are
+/// stored in the table, which differ between the DCX and DCP layouts.
+fn read_table(data_info: &[u8]) -> Result<EdgeTable, DcxError> {
+    if !data_info.starts_with(MAGIC_TABLE) {
+        return Err(DcxError::ParserError);
+    }
+
+    let header_size = field(data_info, 0x8)? as usize;
+    let entry_size = field(data_info, 0xC)? as usize;
+    let block_size = field(data_info, 0x10)? as usize;
+    if header_size < 0x20
+        || entry_size < TABLE_ENTRY_SIZE
+        || block_size == 0
+        || block_size > BLOCK_SIZE
+    {
+        return Err(DcxError::ParserError);
+    }
+
+    // The block count is the second to last field of the header in both layouts.
+    let block_count = field(data_info, header_size - 8)? as usize;
+
+    let blocks = (0..block_count)
+        .map(|index| {
+            let entry = header_size + index * entry_size;
+
+            Ok(EdgeBlock {
+                offset: field(data_info, entry + 0x4)?,
+                size: field(data_info, entry + 0x8)?,
+                compressed: field(data_info, entry + 0xC)? != 0,
+            })
+        })
+        .collect::<Result<_, DcxError>>()?;
+
+    Ok(EdgeTable { block_size, blocks })
+}
+
+fn field(data: &[u8], offset: usize) -> Result<u32, DcxError> {
+    data.get(offset..offset + 4)
+        .and_then(U32::<BE>::read_from)
+        .map(|value| value.get())
+        .ok_or(DcxError::ParserError)
+}
+
+/// Decode all blocks of `data` in parallel.
+pub(crate) fn decompress(
+    data: &[u8],
+    data_info: &[u8],
+    uncompressed_size: usize,
+) -> Result<Vec<u8>, DcxError> {
+    let table = read_table(data_info)?;
+    let blocks = table
+        .blocks
+        .par_iter()
+        .map(|block| {
+            let contents = data.get(block.range()).ok_or(ErrorKind::UnexpectedEof)?;
+
+            let mut decoded = Vec::with_capacity(table.block_size);
+            block.decode(contents, table.block_size, &mut decoded)?;
+
+            Ok(decoded)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    if blocks.iter().map(Vec::len).sum::<usize>() != uncompressed_size {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "EDGE blocks don't add up to the uncompressed size",
+        )
+        .into());
+    }
+
+    let mut output = Vec::with_capacity(uncompressed_size);
+    for block in blocks {
+        output.extend_from_slice(&block);
+    }
+
+    Ok(output)
+}
+
+/// Random access to `EDGE` compressed data. Blocks are decoded when they are first read from and
+/// the most recently used ones are kept, so reads close to each other only decode once.
+pub struct EdgeBlockReader<R: Read + Seek> {
+    source: EdgeSource<R>,
+
+    /// Position of the compressed data in a streaming source.
+    data_start: u64,
+    table: EdgeTable,
+    length: u64,
+    position: u64,
+
+    /// Recently decoded blocks as `(index, contents)`, most recently used first.
+    cache: VecDeque<(usize, Vec<u8>)>,
+
+    /// Compressed contents of the last block read from a stream.
+    compressed: Vec<u8>,
+}
+
+impl<R: Read + Seek> EdgeBlockReader<R> {
+    /// Read blocks from `reader`, which has to be positioned at the start of the compressed data.
+    pub fn new(mut reader: R, data_info: &[u8], uncompressed_size: u64) -> Result<Self, DcxError> {
+        let data_start = reader.stream_position()?;
+
+        Self::with_source(
+            EdgeSource::Stream(reader),
+            data_start,
+            data_info,
+            uncompressed_size,
+        )
+    }
+
+    /// Read blocks from compressed data that was already read.
+    pub fn from_buffer(
+        data: Arc<[u8]>,
+        data_info: &[u8],
+        uncompressed_size: u64,
+    ) -> Result<Self, DcxError> {
+        Self::with_source(EdgeSource::Buffered(data), 0, data_info, uncompressed_size)
+    }
+
+    fn with_source(
+        source: EdgeSource<R>,
+        data_start: u64,
+        data_info: &[u8],
+        uncompressed_size: u64,
+    ) -> Result<Self, DcxError> {
+        Ok(Self {
+            source,
+            data_start,
+            table: read_table(data_info)?,
+            length: uncompressed_size,
+            position: 0,
+            cache: VecDeque::with_capacity(CACHED_BLOCKS),
+            compressed: Vec::new(),
+        })
+    }
+
+    /// The decoded contents of the block at `index`, decoding it if it isn't cached.
+    fn block(&mut self, index: usize) -> io::Result<&[u8]> {
+        if let Some(cached) = self.cache.iter().position(|(cached, _)| *cached == index) {
+            if let Some(entry) = self.cache.remove(cached) {
+                self.cache.push_front(entry);
+            }
+        } else {
+            let block = *self.table.blocks.get(index).ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidData, "EDGE block table is too short")
+            })?;
+
+            let contents: &[u8] = match &mut self.source {
+                EdgeSource::Stream(reader) => {
+                    reader.seek(SeekFrom::Start(self.data_start + block.offset as u64))?;
+                    self.compressed.resize(block.size as usize, 0);
+                    reader.read_exact(&mut self.compressed)?;
+
+                    &self.compressed
+                }
+                EdgeSource::Buffered(data) => {
+                    data.get(block.range()).ok_or(ErrorKind::UnexpectedEof)?
+                }
+            };
+
+            // Reuse the allocation of the least recently used block once the cache is full.
+            let mut decoded = if self.cache.len() == CACHED_BLOCKS {
+                self.cache.pop_back().map(|(_, decoded)| decoded)
+            } else {
+                None
+            }
+            .unwrap_or_default();
+
+            decoded.clear();
+            block.decode(contents, self.table.block_size, &mut decoded)?;
+
+            self.cache.push_front((index, decoded));
+        }
+
+        Ok(&self.cache[0].1)
+    }
+}
+
+impl<R: Read + Seek> Read for EdgeBlockReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.length || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let block_size = self.table.block_size as u64;
+        let index = (self.position / block_size) as usize;
+        let offset = (self.position % block_size) as usize;
+        let remaining = (self.length - self.position).min(usize::MAX as u64) as usize;
+
+        let block = self.block(index)?;
+        let available = match block.get(offset..) {
+            Some(available) if !available.is_empty() => available,
+            _ => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "EDGE block is shorter than the block size",
+                ))
+            }
+        };
+
+        let read = available.len().min(buf.len()).min(remaining);
+        buf[..read].copy_from_slice(&available[..read]);
+        self.position += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for EdgeBlockReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "invalid seek offset"))?;
+
+        self.position = position;
+
+        Ok(position)
+    }
+}
+
+/// Compress `data` into blocks, returning the block table for the DCA chunk and the compressed
+/// data.
+pub(crate) fn compress(data: &[u8], level: u32) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let block_count = data.len().div_ceil(BLOCK_SIZE);
+
+    // Like the games, zero when the last block is a full one.
+    let last_block_size = data.len() % BLOCK_SIZE;
+    let table_size = TABLE_HEADER_SIZE + block_count * TABLE_ENTRY_SIZE;
+
+    let mut table = Vec::with_capacity(table_size);
+    table.extend_from_slice(MAGIC_TABLE);
+
+    for value in [
+        TABLE_VERSION,
+        TABLE_HEADER_SIZE as u32,
+        TABLE_ENTRY_SIZE as u32,
+        BLOCK_SIZE as u32,
+        last_block_size as u32,
+        table_size as u32,
+        block_count as u32,
+        0x100000,
+    ] {
+        table.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut compressed = Vec::with_capacity(data.len() / 2);
+    for block in data.chunks(BLOCK_SIZE) {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(block)?;
+        let deflated = encoder.finish()?;
+
+        let is_compressed = deflated.len() < block.len();
+        let contents = if is_compressed { &deflated[..] } else { block };
+
+        let offset = u32::try_from(compressed.len())
+            .map_err(|_| io::Error::other("EDGE contents exceed 4GiB"))?;
+
+        for value in [0, offset, contents.len() as u32, is_compressed as u32] {
+            table.extend_from_slice(&value.to_be_bytes());
+        }
+
+        compressed.extend_from_slice(contents);
+    }
+
+    Ok((table, compressed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compress, field, read_table, BLOCK_SIZE};
+
+    #[test]
+    fn writes_block_table() {
+        let (table, _) = compress(&[0x1u8; BLOCK_SIZE * 2], 9).expect("failed to compress");
+        assert_eq!(field(&table, 0x10).ok(), Some(BLOCK_SIZE as u32));
+        assert_eq!(field(&table, 0x14).ok(), Some(0));
+
+        let (table, _) = compress(&[0x1u8; BLOCK_SIZE + 5], 9).expect("failed to compress");
+        assert_eq!(field(&table, 0x14).ok(), Some(5));
+    }
+
+    #[test]
+    fn rejects_oversized_blocks() {
+        let (mut table, _) = compress(&[0x1u8; 5], 9).expect("failed to compress");
+        assert!(read_table(&table).is_ok());
+
+        table[0x10..0x14].copy_from_slice(&(BLOCK_SIZE as u32 * 2).to_be_bytes());
+        assert!(read_table(&table).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    io::{self, Write},
+    mem::size_of,
+};
+
+use flate2::{write::ZlibEncoder, Compression};
+use fstools_oodle_rt::{CompressionLevel, Compressor, Oodle};
+use zerocopy::U32;
+
+use super::{
+    edge, Additional, CompressionParameters, CompressionParametersHeader, DcxError, DcxHeader,
+    DcxLayout, Metadata, Sizes, MAGIC_ALGORITHM_DEFLATE, MAGIC_ALGORITHM_EDGE,
+    MAGIC_ALGORITHM_KRAKEN, MAGIC_ALGORITHM_ZSTD, MAGIC_DCS, MAGIC_DCX,
+};
+
+const DCX_VERSION: u32 = 0x11000;
+
+/// Trailing bytes of the DCP settings, identical for every algorithm written by the games.
+const SETTINGS_TRAILER: [u8; 4] = [0x0, 0x1, 0x1, 0x0];
+
+/// `EDGE` is only found in PS3 games, which use an older container version and settings trailer.
+const EDGE_DCX_VERSION: u32 = 0x10000;
+const EDGE_SETTINGS_TRAILER: [u8; 4] = [0x0, 0x10, 0x1, 0x0];
+
+/// Size of the DCP settings written by the games, with the level first and the trailer last.
+const SETTINGS_SIZE: usize = 20;
+
+/// Size of the chunks preceding the compressed data.
+const HEADER_SIZE: usize = 0x4C;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DcxAlgorithm {
+    Deflate,
+    Zstd,
+    Kraken,
+    Edge,
+}
+
+/// Compresses data into a DCX container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DcxEncoder {
+    algorithm: DcxAlgorithm,
+
+    /// Compression level, stored as the first byte of the DCP settings.
+    level: u8,
+
+    /// The Oodle compressor used for `KRAK`. The container only records that Oodle was used, the
+    /// compressor is stored in the compressed data itself.
+    oodle_compressor: Compressor,
+}
+
+impl DcxEncoder {
+    /// Create an encoder using the default compression level for `algorithm`, matching the values
+    /// found in Elden Ring (`DFLT` 9, `ZSTD` 21, `KRAK` 6) and the PS3 games (`EDGE` 9).
+    pub fn new(algorithm: DcxAlgorithm) -> Self {
+        let level = match algorithm {
+            DcxAlgorithm::Deflate | DcxAlgorithm::Edge => 9,
+            DcxAlgorithm::Zstd => 0x15,
+            DcxAlgorithm::Kraken => 6,
+        };
+
+        Self {
+            algorithm,
+            level,
+            oodle_compressor: Compressor::OodleLZ_Compressor_Kraken,
+        }
+    }
+
+    pub fn with_level(mut self, level: u8) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Compress `KRAK` containers with another Oodle compressor than Kraken, e.g. Leviathan for
+    /// smaller files or Mermaid for faster decompression.
+    pub fn with_oodle_compressor(mut self, compressor: Compressor) -> Self {
+        self.oodle_compressor = compressor;
+        self
+    }
+
+    pub fn algorithm(&self) -> DcxAlgorithm {
+        self.algorithm
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Compress `data` and write the resulting DCX container to `w`.
+    pub fn encode<W: Write>(&self, data: &[u8], mut w: W) -> Result<(), DcxError> {
+        let (compressed, data_info) = self.compress(data)?;
+        let header = self.header(data.len(), compressed.len(), data_info)?;
+
+        header.write(&mut w)?;
+        w.write_all(&compressed)?;
+
+        Ok(())
+    }
+
+    pub fn encode_to_vec(&self, data: &[u8]) -> Result<Vec<u8>, DcxError> {
+        let mut output = Vec::with_capacity(HEADER_SIZE + data.len() / 2);
+        self.encode(data, &mut output)?;
+
+        Ok(output)
+    }
+
+    /// Create a writer that compresses everything written to it once finished. Nothing is written
+    /// to `inner` unless [`DcxWriter::finish`] is called.
+    pub fn writer<W: Write>(self, inner: W) -> DcxWriter<W> {
+        DcxWriter {
+            encoder: self,
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Compress `data`, returning the compressed data and the contents of the DCA chunk.
+    fn compress(&self, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), DcxError> {
+        let compressed = match self.algorithm {
+            DcxAlgorithm::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(self.level as u32));
+                encoder.write_all(data)?;
+
+                encoder.finish()?
+            }
+            DcxAlgorithm::Zstd => zstd::bulk::compress(data, self.level as i32)?,
+            DcxAlgorithm::Kraken => {
+                let level =
+                    CompressionLevel::from_raw(self.level as i32).ok_or(DcxError::EncoderError)?;
+
+                Oodle::current()?.compress(self.oodle_compressor, level, data)?
+            }
+            DcxAlgorithm::Edge => {
+                let (table, compressed) = edge::compress(data, self.level as u32)?;
+                return Ok((compressed, table));
+            }
+        };
+
+        Ok((compressed, Vec::new()))
+    }
+
+    fn header(
+        &self,
+        uncompressed_size: usize,
+        compressed_size: usize,
+        data_info: Vec<u8>,
+    ) -> Result<DcxHeader, DcxError> {
+        let size = |value: usize| {
+            u32::try_from(value)
+                .map(U32::new)
+                .map_err(|_| io::Error::other("DCX contents exceed 4GiB"))
+        };
+
+        let algorithm = match self.algorithm {
+            DcxAlgorithm::Deflate => MAGIC_ALGORITHM_DEFLATE,
+            DcxAlgorithm::Zstd => MAGIC_ALGORITHM_ZSTD,
+            DcxAlgorithm::Kraken => MAGIC_ALGORITHM_KRAKEN,
+            DcxAlgorithm::Edge => MAGIC_ALGORITHM_EDGE,
+        };
+
+        let mut settings = vec![0u8; SETTINGS_SIZE];
+        settings[0] = self.level;
+
+        let (version, trailer) = if self.algorithm == DcxAlgorithm::Edge {
+            settings[4..8].copy_from_slice(&(edge::BLOCK_SIZE as u32).to_be_bytes());
+            (EDGE_DCX_VERSION, EDGE_SETTINGS_TRAILER)
+        } else {
+            (DCX_VERSION, SETTINGS_TRAILER)
+        };
+        settings[SETTINGS_SIZE - trailer.len()..].copy_from_slice(&trailer);
+
+        let sizes_offset = size_of::<Metadata>();
+        let params_offset = sizes_offset + size_of::<Sizes>();
+        let data_info_offset =
+            params_offset + size_of::<CompressionParametersHeader>() + SETTINGS_SIZE;
+        let data_offset = data_info_offset + size_of::<Additional>() + data_info.len();
+
+        Ok(DcxHeader {
+            layout: DcxLayout::Dcx,
+            metadata: Some(Metadata {
+                chunk_magic: *MAGIC_DCX,
+                version: U32::new(version),
+                sizes_offset: size(sizes_offset)?,
+                params_offset: size(params_offset)?,
+                data_info_offset: size(data_info_offset)?,
+                data_offset: size(data_offset)?,
+            }),
+            sizes: Sizes {
+                chunk_magic: *MAGIC_DCS,
+                uncompressed_size: size(uncompressed_size)?,
+                compressed_size: size(compressed_size)?,
+            },
+            compression_parameters: CompressionParameters {
+                algorithm: *algorithm,
+                settings,
+            },
+            data_info,
+            buffered_data: None,
+        })
+    }
+}
+
+/// Buffers everything written to it and writes a compressed DCX container to the inner writer
+/// when [`DcxWriter::finish`] is called.
+///
+/// The container can only be written once all data is known, so [`Write::flush`] doesn't write
+/// anything and dropping the writer without calling [`DcxWriter::finish`] discards the data.
+#[must_use = "data is only written when `DcxWriter::finish` is called"]
+pub struct DcxWriter<W: Write> {
+    encoder: DcxEncoder,
+    inner: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> DcxWriter<W> {
+    /// Compress the buffered data, write the container and return the inner writer.
+    pub fn finish(mut self) -> Result<W, DcxError> {
+        self.encoder.encode(&self.buffer, &mut self.inner)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for DcxWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.write(buf)
+    }
+
+    /// Does nothing, the data is compressed and written by [`DcxWriter::finish`].
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use fstools_oodle_rt::{Compressor, Oodle};
+
+    use super::{DcxAlgorithm, DcxEncoder};
+    use crate::dcx::DcxHeader;
+
+    #[test]
+    fn round_trips() {
+        let data: Vec<u8> = (0..0x24000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut encoders = vec![
+            DcxEncoder::new(DcxAlgorithm::Deflate),
+            DcxEncoder::new(DcxAlgorithm::Zstd),
+            DcxEncoder::new(DcxAlgorithm::Edge),
+        ];
+        if Oodle::current().is_ok_and(|oodle| oodle.can_compress()) {
+            let kraken = DcxEncoder::new(DcxAlgorithm::Kraken);
+
+            encoders.push(kraken);
+            encoders.push(kraken.with_oodle_compressor(Compressor::OodleLZ_Compressor_Leviathan));
+        }
+
+        for encoder in encoders {
+            let encoded = encoder.encode_to_vec(&data).expect("failed to encode DCX");
+
+            let (header, mut decoder) = DcxHeader::read(&encoded[..]).expect("invalid DCX header");
+            assert_eq!(header.sizes().uncompressed_size() as usize, data.len());
+
+            let mut decoded = Vec::new();
+            decoder
+                .read_to_end(&mut decoded)
+                .expect("failed to decode DCX");
+
+            assert_eq!(decoded, data);
+        }
+    }
+}
@@ -1,24 +1,44 @@
 use std::{
     fmt::{Debug, Formatter},
-    io::{Error, Read},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
     mem::size_of,
+    sync::Arc,
 };
 
 use byteorder::BE;
+use fstools_oodle_rt::{Oodle, OodleError};
 use thiserror::Error;
-use zerocopy::{FromBytes, FromZeroes, U32};
+use zerocopy::{AsBytes, FromBytes, FromZeroes, U32};
 use zstd::ZstdDecoder;
 
-use self::{deflate::DeflateDecoder, oodle::OodleReader};
+pub use self::encoder::{DcxAlgorithm, DcxEncoder, DcxWriter};
+use self::{
+    deflate::DeflateDecoder,
+    edge::{EdgeBlockReader, EdgeDecoder},
+    oodle::OodleReader,
+};
 
 pub mod deflate;
+pub mod edge;
+mod encoder;
 pub mod oodle;
 pub mod zstd;
 
-const MAGIC_DCX: u32 = 0x44435800;
+const MAGIC_DCX: &[u8; 4] = b"DCX\0";
+const MAGIC_DCS: &[u8; 4] = b"DCS\0";
+const MAGIC_DCP: &[u8; 4] = b"DCP\0";
+const MAGIC_DCA: &[u8; 4] = b"DCA\0";
 const MAGIC_ALGORITHM_KRAKEN: &[u8; 4] = b"KRAK";
 const MAGIC_ALGORITHM_DEFLATE: &[u8; 4] = b"DFLT";
 const MAGIC_ALGORITHM_ZSTD: &[u8; 4] = b"ZSTD";
+const MAGIC_ALGORITHM_EDGE: &[u8; 4] = b"EDGE";
+
+/// Upper bound for the size of the DCP and DCA chunks, which only hold settings and block tables.
+const MAX_CHUNK_SIZE: u32 = 0x100000;
+
+/// Upper bound on how much larger compressed data is expected to get when decompressed, used to
+/// limit how much memory is reserved up front for sizes read from headers.
+pub(crate) const MAX_COMPRESSION_RATIO: u64 = 16;
 
 #[derive(Debug, Error)]
 pub enum DcxError {
@@ -33,6 +53,15 @@ pub enum DcxError {
 
     #[error("Unable to create compression codec for DCX contents")]
     DecoderError,
+
+    #[error("Oodle error: {0}")]
+    Oodle(#[from] OodleError),
+
+    #[error("Unable to compress DCX contents")]
+    EncoderError,
+
+    #[error("Data decompressed to {actual:#x} bytes instead of {expected:#x}")]
+    UncompressedSizeMismatch { expected: u64, actual: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -44,37 +73,186 @@ pub enum DecompressionError {
     Zlib,
 }
 
-#[derive(FromBytes, FromZeroes)]
-#[repr(packed)]
+/// Order of the chunks in a DCX container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DcxLayout {
+    /// A DCX chunk followed by the DCS, DCP and DCA chunks and the compressed data. Used by every
+    /// game since Dark Souls.
+    Dcx,
+
+    /// The DCP and DCS chunks followed by the compressed data and a trailing DCA chunk, without a
+    /// DCX chunk. Used by Demon's Souls.
+    Dcp,
+}
+
 pub struct DcxHeader {
-    metadata: Metadata,
+    layout: DcxLayout,
+
+    /// Only present in the [`DcxLayout::Dcx`] layout.
+    metadata: Option<Metadata>,
     sizes: Sizes,
     compression_parameters: CompressionParameters,
-    _additional: Additional,
+
+    /// Contents of the DCA chunk following its size, e.g. the block table of `EDGE` compressed
+    /// data.
+    data_info: Vec<u8>,
+
+    /// The compressed data, for the [`DcxLayout::Dcp`] layout of `EDGE` where the data has to be
+    /// read to get to the block table in the DCA chunk following it.
+    buffered_data: Option<Arc<[u8]>>,
 }
 
 impl DcxHeader {
     pub fn read<R: Read>(mut reader: R) -> Result<(DcxHeader, DcxContentDecoder<R>), DcxError> {
-        let mut header_data = [0u8; size_of::<DcxHeader>()];
-        reader.read_exact(&mut header_data)?;
-
-        let dcx = DcxHeader::read_from(&header_data).ok_or(Error::other("unaligned DCX header"))?;
+        let dcx = DcxHeader::read_header(&mut reader)?;
         let decoder = dcx.create_decoder(reader)?;
 
         Ok((dcx, decoder))
     }
 
+    /// Decompress a whole container held in memory. Faster than reading from the decoder returned
+    /// by [`DcxHeader::read`] when all of the contents are needed, see [`DcxHeader::decompress`].
+    pub fn decompress_to_vec(container: &[u8]) -> Result<(DcxHeader, Vec<u8>), DcxError> {
+        let mut data = container;
+        let dcx = DcxHeader::read_header(&mut data)?;
+        let contents = dcx.decompress(data)?;
+
+        Ok((dcx, contents))
+    }
+
+    /// Read a container for random access. `EDGE` blocks are decoded as they are read, other
+    /// algorithms can't be decoded from the middle and are decompressed up front.
+    pub fn read_seekable<R: Read + Seek>(
+        mut reader: R,
+    ) -> Result<(DcxHeader, DcxSeekableReader<R>), DcxError> {
+        let dcx = DcxHeader::read_header(&mut reader)?;
+        let uncompressed_size = dcx.sizes.uncompressed_size.get() as u64;
+
+        let seekable = if dcx.compression_parameters.algorithm == *MAGIC_ALGORITHM_EDGE {
+            DcxSeekableReader::Blocks(match &dcx.buffered_data {
+                Some(data) => {
+                    EdgeBlockReader::from_buffer(data.clone(), &dcx.data_info, uncompressed_size)?
+                }
+                None => EdgeBlockReader::new(reader, &dcx.data_info, uncompressed_size)?,
+            })
+        } else {
+            let mut data = Vec::new();
+            reader
+                .take(dcx.sizes.compressed_size.get() as u64)
+                .read_to_end(&mut data)?;
+
+            DcxSeekableReader::Decompressed(Cursor::new(dcx.decompress(&data)?))
+        };
+
+        Ok((dcx, seekable))
+    }
+
+    /// Parse the chunks in front of the compressed data, leaving `reader` at its start.
+    ///
+    /// Chunks are read in order using the sizes stored in them, skipping ahead to the offsets in
+    /// the DCX chunk when they point past the end of the previous chunk. Offsets pointing backwards
+    /// are ignored, since the offsets of the DCA chunk and the data aren't relative to the start of
+    /// the file in older games.
+    pub fn read_header<R: Read>(mut reader: R) -> Result<DcxHeader, DcxError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let layout = match &magic {
+            MAGIC_DCX => DcxLayout::Dcx,
+            MAGIC_DCP => DcxLayout::Dcp,
+            _ => return Err(DcxError::ParserError),
+        };
+
+        let mut reader = magic.as_slice().chain(reader);
+
+        match layout {
+            DcxLayout::Dcx => {
+                let metadata: Metadata = read_chunk(&mut reader, MAGIC_DCX)?;
+                let mut position = size_of::<Metadata>() as u64;
+
+                skip_to(&mut reader, &mut position, metadata.sizes_offset.get())?;
+                let sizes: Sizes = read_chunk(&mut reader, MAGIC_DCS)?;
+                position += size_of::<Sizes>() as u64;
+
+                skip_to(&mut reader, &mut position, metadata.params_offset.get())?;
+                let compression_parameters = CompressionParameters::read(&mut reader)?;
+                position += (size_of::<CompressionParametersHeader>()
+                    + compression_parameters.settings.len()) as u64;
+
+                skip_to(&mut reader, &mut position, metadata.data_info_offset.get())?;
+                let data_info = read_data_info(&mut reader)?;
+                position += (size_of::<Additional>() + data_info.len()) as u64;
+
+                skip_to(&mut reader, &mut position, metadata.data_offset.get())?;
+
+                Ok(DcxHeader {
+                    layout,
+                    metadata: Some(metadata),
+                    sizes,
+                    compression_parameters,
+                    data_info,
+                    buffered_data: None,
+                })
+            }
+            DcxLayout::Dcp => {
+                let compression_parameters = CompressionParameters::read(&mut reader)?;
+                let sizes: Sizes = read_chunk(&mut reader, MAGIC_DCS)?;
+
+                if compression_parameters.algorithm != *MAGIC_ALGORITHM_EDGE {
+                    return Ok(DcxHeader {
+                        layout,
+                        metadata: None,
+                        sizes,
+                        compression_parameters,
+                        data_info: Vec::new(),
+                        buffered_data: None,
+                    });
+                }
+
+                // The data of EDGE starts after 4 bytes of padding.
+                let mut padding = [0u8; 4];
+                reader.read_exact(&mut padding)?;
+
+                let compressed_size = sizes.compressed_size.get() as u64;
+                let mut data = Vec::new();
+                if reader
+                    .by_ref()
+                    .take(compressed_size)
+                    .read_to_end(&mut data)? as u64
+                    != compressed_size
+                {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+
+                let data_info = read_data_info(&mut reader)?;
+
+                Ok(DcxHeader {
+                    layout,
+                    metadata: None,
+                    sizes,
+                    compression_parameters,
+                    data_info,
+                    buffered_data: Some(data.into()),
+                })
+            }
+        }
+    }
+
     pub fn create_decoder<R: Read>(&self, reader: R) -> Result<DcxContentDecoder<R>, DcxError> {
         let algorithm = &self.compression_parameters.algorithm;
         let decoder = match algorithm {
-            MAGIC_ALGORITHM_KRAKEN => Decoder::Kraken(
-                OodleReader::new(reader, self.sizes.uncompressed_size.get())
-                    .ok_or(DcxError::DecoderError)?,
-            ),
+            MAGIC_ALGORITHM_KRAKEN => Decoder::Kraken(OodleReader::new(
+                reader,
+                self.sizes.uncompressed_size.get(),
+            )?),
             MAGIC_ALGORITHM_DEFLATE => Decoder::Deflate(DeflateDecoder::new(reader)),
             MAGIC_ALGORITHM_ZSTD => {
                 Decoder::Zstd(ZstdDecoder::new(reader).map_err(|_| DcxError::DecoderError)?)
             }
+            MAGIC_ALGORITHM_EDGE => Decoder::Edge(match &self.buffered_data {
+                Some(data) => EdgeDecoder::from_buffer(data.clone(), &self.data_info)?,
+                None => EdgeDecoder::new(reader, &self.data_info)?,
+            }),
             _ => return Err(DcxError::UnknownAlgorithm(algorithm.to_owned())),
         };
 
@@ -84,15 +262,57 @@ impl DcxHeader {
         })
     }
 
-    pub fn has_magic(buf: &[u8]) -> bool {
-        match U32::<BE>::ref_from_prefix(buf) {
-            Some(v) => v.get() == MAGIC_DCX,
-            None => false,
+    /// Decompress the `data` following the header in one go, allocating the output once using the
+    /// uncompressed size. `KRAK` is decoded with a single call into Oodle and the blocks of `EDGE`
+    /// are decoded in parallel, other algorithms are streamed. Fails unless the data decompresses
+    /// to exactly the uncompressed size.
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, DcxError> {
+        let uncompressed_size = self.sizes.uncompressed_size.get() as usize;
+        let compressed_size = self.sizes.compressed_size.get() as usize;
+
+        let data = self
+            .buffered_data
+            .as_deref()
+            .unwrap_or(data)
+            .get(..compressed_size)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+        match &self.compression_parameters.algorithm {
+            MAGIC_ALGORITHM_KRAKEN => Ok(Oodle::current()?.decompress(data, uncompressed_size)?),
+            MAGIC_ALGORITHM_EDGE => edge::decompress(data, &self.data_info, uncompressed_size),
+            _ => {
+                // The size comes from the header, so don't reserve more than the data could
+                // plausibly expand to and stop reading once it's exceeded.
+                let capacity = uncompressed_size
+                    .min(data.len().saturating_mul(MAX_COMPRESSION_RATIO as usize));
+                let mut contents = Vec::with_capacity(capacity);
+                self.create_decoder(data)?
+                    .take(uncompressed_size as u64 + 1)
+                    .read_to_end(&mut contents)?;
+
+                if contents.len() != uncompressed_size {
+                    return Err(DcxError::UncompressedSizeMismatch {
+                        expected: uncompressed_size as u64,
+                        actual: contents.len() as u64,
+                    });
+                }
+
+                Ok(contents)
+            }
         }
     }
 
-    pub fn metadata(&self) -> &Metadata {
-        &self.metadata
+    /// Whether `buf` starts with either of the chunks a DCX container can start with.
+    pub fn has_magic(buf: &[u8]) -> bool {
+        buf.starts_with(MAGIC_DCX) || buf.starts_with(MAGIC_DCP)
+    }
+
+    pub fn layout(&self) -> DcxLayout {
+        self.layout
+    }
+
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
     }
 
     pub fn sizes(&self) -> &Sizes {
@@ -102,11 +322,47 @@ impl DcxHeader {
     pub fn compression_parameters(&self) -> &CompressionParameters {
         &self.compression_parameters
     }
+
+    /// Contents of the DCA chunk following its size.
+    pub fn data_info(&self) -> &[u8] {
+        &self.data_info
+    }
+
+    /// Write the chunks preceding the compressed data.
+    fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        if let Some(metadata) = &self.metadata {
+            w.write_all(metadata.as_bytes())?;
+        }
+
+        match self.layout {
+            DcxLayout::Dcx => {
+                w.write_all(self.sizes.as_bytes())?;
+                self.compression_parameters.write(&mut w)?;
+
+                let additional_size = size_of::<Additional>() + self.data_info.len();
+                let additional = Additional {
+                    chunk_magic: *MAGIC_DCA,
+                    chunk_size: U32::new(
+                        u32::try_from(additional_size)
+                            .map_err(|_| io::Error::other("DCA chunk exceeds 4GiB"))?,
+                    ),
+                };
+
+                w.write_all(additional.as_bytes())?;
+                w.write_all(&self.data_info)
+            }
+            DcxLayout::Dcp => {
+                self.compression_parameters.write(&mut w)?;
+                w.write_all(self.sizes.as_bytes())
+            }
+        }
+    }
 }
 
 impl Debug for DcxHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DCX")
+            .field("layout", &self.layout)
             .field("header", &self.metadata)
             .field("sizes", &self.sizes)
             .field("compression_parameters", &self.compression_parameters)
@@ -114,10 +370,91 @@ impl Debug for DcxHeader {
     }
 }
 
+/// Read a fixed size chunk, checking that it starts with `magic`.
+fn read_chunk<T: AsBytes + FromBytes, R: Read>(
+    reader: &mut R,
+    magic: &[u8; 4],
+) -> Result<T, DcxError> {
+    let mut chunk = T::new_zeroed();
+    reader.read_exact(chunk.as_bytes_mut())?;
+
+    if !chunk.as_bytes().starts_with(magic) {
+        return Err(DcxError::ParserError);
+    }
+
+    Ok(chunk)
+}
+
+/// Read the DCA chunk, returning its contents following the size.
+fn read_data_info<R: Read>(reader: &mut R) -> Result<Vec<u8>, DcxError> {
+    let additional: Additional = read_chunk(reader, MAGIC_DCA)?;
+
+    let mut data_info = vec![0u8; chunk_contents_size(additional.chunk_size, &additional)?];
+    reader.read_exact(&mut data_info)?;
+
+    Ok(data_info)
+}
+
+/// The number of bytes following the fixed part `header` of a chunk of `chunk_size` bytes.
+fn chunk_contents_size<T: AsBytes>(chunk_size: U32<BE>, header: &T) -> Result<usize, DcxError> {
+    let chunk_size = chunk_size.get();
+    if chunk_size > MAX_CHUNK_SIZE {
+        return Err(DcxError::ParserError);
+    }
+
+    (chunk_size as usize)
+        .checked_sub(header.as_bytes().len())
+        .ok_or(DcxError::ParserError)
+}
+
+/// Skip ahead to `offset` if `position` is before it. Offsets pointing backwards are ignored, the
+/// next chunk is expected to follow directly instead.
+fn skip_to<R: Read>(reader: &mut R, position: &mut u64, offset: u32) -> io::Result<()> {
+    let offset = offset as u64;
+    if offset > *position {
+        let padding = offset - *position;
+        if io::copy(&mut reader.take(padding), &mut io::sink())? != padding {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        *position = offset;
+    }
+
+    Ok(())
+}
+
 pub enum Decoder<R: Read> {
     Kraken(OodleReader<R>),
     Deflate(DeflateDecoder<R>),
     Zstd(ZstdDecoder<R>),
+    Edge(EdgeDecoder<R>),
+}
+
+/// Random access to the contents of a container, see [`DcxHeader::read_seekable`].
+pub enum DcxSeekableReader<R: Read + Seek> {
+    /// `EDGE` blocks, decoded as they are read.
+    Blocks(EdgeBlockReader<R>),
+
+    /// Contents decompressed up front.
+    Decompressed(Cursor<Vec<u8>>),
+}
+
+impl<R: Read + Seek> Read for DcxSeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            DcxSeekableReader::Blocks(r) => r.read(buf),
+            DcxSeekableReader::Decompressed(r) => r.read(buf),
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for DcxSeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            DcxSeekableReader::Blocks(r) => r.seek(pos),
+            DcxSeekableReader::Decompressed(r) => r.seek(pos),
+        }
+    }
 }
 
 pub struct DcxContentDecoder<R: Read> {
@@ -139,11 +476,12 @@ impl<R: Read> Read for DcxContentDecoder<R> {
             Decoder::Kraken(d) => d.read(buf),
             Decoder::Deflate(d) => d.read(buf),
             Decoder::Zstd(d) => d.read(buf),
+            Decoder::Edge(d) => d.read(buf),
         }
     }
 }
 
-#[derive(FromZeroes, FromBytes)]
+#[derive(AsBytes, FromZeroes, FromBytes)]
 #[repr(C)]
 #[allow(unused)]
 /// The DCX chunk. Describes the layout of the container.
@@ -166,6 +504,13 @@ pub struct Metadata {
     data_offset: U32<BE>,
 }
 
+impl Metadata {
+    /// Version of the container, `0x10000` in Dark Souls and `0x11000` in later games.
+    pub fn version(&self) -> u32 {
+        self.version.get()
+    }
+}
+
 impl Debug for Metadata {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Metadata")
@@ -174,7 +519,7 @@ impl Debug for Metadata {
     }
 }
 
-#[derive(FromZeroes, FromBytes)]
+#[derive(AsBytes, FromZeroes, FromBytes)]
 #[repr(C)]
 #[allow(unused)]
 /// The DCS Chunk. Describes the sizes before and after compression.
@@ -188,6 +533,16 @@ pub struct Sizes {
     compressed_size: U32<BE>,
 }
 
+impl Sizes {
+    pub fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size.get()
+    }
+
+    pub fn compressed_size(&self) -> u32 {
+        self.compressed_size.get()
+    }
+}
+
 impl Debug for Sizes {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Sizes")
@@ -197,22 +552,56 @@ impl Debug for Sizes {
     }
 }
 
-#[derive(FromZeroes, FromBytes)]
-#[repr(packed)]
-#[allow(unused)]
 /// The DCP chunk. Describes parameters used for compression/decompression.
 pub struct CompressionParameters {
-    chunk_magic: [u8; 4],
-
     /// Either KRAK, DFLT, EDGE or ZSTD
     algorithm: [u8; 4],
 
-    /// Seems to the size of the current DCP chunk including magic and algo
-    chunk_size: U32<BE>,
+    /// Arbitrary bytes describing the parameter chunk, starting with the compression level.
+    settings: Vec<u8>,
+}
+
+impl CompressionParameters {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, DcxError> {
+        let header: CompressionParametersHeader = read_chunk(reader, MAGIC_DCP)?;
+
+        let mut settings = vec![0u8; chunk_contents_size(header.chunk_size, &header)?];
+        reader.read_exact(&mut settings)?;
+
+        Ok(Self {
+            algorithm: header.algorithm,
+            settings,
+        })
+    }
 
-    /// Arbitrary bytes describing the parameter chunk
-    /// TODO make this of dynamic size using the chunksize
-    settings: [u8; 20],
+    fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let chunk_size = size_of::<CompressionParametersHeader>() + self.settings.len();
+        let header = CompressionParametersHeader {
+            chunk_magic: *MAGIC_DCP,
+            algorithm: self.algorithm,
+            chunk_size: U32::new(
+                u32::try_from(chunk_size)
+                    .map_err(|_| io::Error::other("DCP chunk exceeds 4GiB"))?,
+            ),
+        };
+
+        w.write_all(header.as_bytes())?;
+        w.write_all(&self.settings)
+    }
+
+    pub fn algorithm(&self) -> &[u8; 4] {
+        &self.algorithm
+    }
+
+    /// The level the data was compressed with, e.g. 9 for `DFLT` or 6 for `KRAK` in Dark Souls 3
+    /// and Sekiro.
+    pub fn level(&self) -> u8 {
+        self.settings.first().copied().unwrap_or_default()
+    }
+
+    pub fn settings(&self) -> &[u8] {
+        &self.settings
+    }
 }
 
 impl Debug for CompressionParameters {
@@ -221,17 +610,304 @@ impl Debug for CompressionParameters {
 
         f.debug_struct("CompressionParameters")
             .field("algorithm", &algorithm_name)
+            .field("level", &self.level())
             .finish()
     }
 }
 
-#[derive(FromZeroes, FromBytes, Debug)]
+#[derive(AsBytes, FromZeroes, FromBytes)]
+#[repr(C)]
+/// The fixed part of the DCP chunk, followed by settings filling the rest of the chunk.
+struct CompressionParametersHeader {
+    chunk_magic: [u8; 4],
+    algorithm: [u8; 4],
+
+    /// Size of the DCP chunk including magic and algorithm
+    chunk_size: U32<BE>,
+}
+
+#[derive(AsBytes, FromZeroes, FromBytes, Debug)]
 #[repr(C)]
 #[allow(unused)]
-/// The DCA chunk. Describes ???
+/// The DCA chunk. Describes the compressed data, e.g. with the block table of `EDGE`.
 struct Additional {
     chunk_magic: [u8; 4],
 
-    /// Seems to the size of the current DCA chunk including magic
+    /// Size of the DCA chunk including magic, followed by the rest of its contents
     chunk_size: U32<BE>,
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+
+    use flate2::{
+        write::{DeflateEncoder, ZlibEncoder},
+        Compression,
+    };
+    use fstools_oodle_rt::Oodle;
+
+    use super::{DcxAlgorithm, DcxEncoder, DcxError, DcxHeader, DcxLayout};
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).expect("failed to compress");
+        encoder.finish().expect("failed to compress")
+    }
+
+    fn chunk(magic: &[u8; 4], fields: &[u32]) -> Vec<u8> {
+        let mut chunk = magic.to_vec();
+        chunk.extend(fields.iter().flat_map(|field| field.to_be_bytes()));
+        chunk
+    }
+
+    #[test]
+    fn reads_older_layouts() {
+        let data: Vec<u8> = (0..0x4000u32).map(|i| (i % 13) as u8).collect();
+        let compressed = compress(&data);
+        let sizes = [data.len() as u32, compressed.len() as u32];
+
+        // Dark Souls, whose DCA and data offsets in the DCX chunk don't match their positions.
+        let dark_souls = [
+            chunk(b"DCX\0", &[0x10000, 0x18, 0x24, 0x24, 0x2C]),
+            chunk(b"DCS\0", &sizes),
+            chunk(
+                b"DCP\0",
+                &[
+                    u32::from_be_bytes(*b"DFLT"),
+                    0x20,
+                    0x9000000,
+                    0,
+                    0,
+                    0,
+                    0x10100,
+                ],
+            ),
+            chunk(b"DCA\0", &[8]),
+            compressed.clone(),
+        ]
+        .concat();
+
+        // Demon's Souls, without a DCX chunk and with the DCA chunk after the data.
+        let demons_souls = [
+            chunk(
+                b"DCP\0",
+                &[
+                    u32::from_be_bytes(*b"DFLT"),
+                    0x20,
+                    0x9000000,
+                    0,
+                    0,
+                    0,
+                    0x10100,
+                ],
+            ),
+            chunk(b"DCS\0", &sizes),
+            compressed,
+            chunk(b"DCA\0", &[8]),
+        ]
+        .concat();
+
+        // Demon's Souls EDGE, with a deflated block followed by a stored one and the block table
+        // after the data.
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(&data[..0x2000])
+            .expect("failed to compress");
+        let mut blocks = encoder.finish().expect("failed to compress");
+        let deflated_size = blocks.len() as u32;
+        blocks.extend_from_slice(&data[0x2000..]);
+
+        let edge_sizes = [data.len() as u32, blocks.len() as u32];
+        let demons_souls_edge = [
+            chunk(
+                b"DCP\0",
+                &[
+                    u32::from_be_bytes(*b"EDGE"),
+                    0x20,
+                    0x9000000,
+                    0x10000,
+                    0,
+                    0,
+                    0x100100,
+                ],
+            ),
+            chunk(b"DCS\0", &edge_sizes),
+            vec![0; 4],
+            blocks,
+            chunk(b"DCA\0", &[0x48]),
+            chunk(b"EgdT", &[0x10000, 0x20, 0x10, 0x10000, 0x40, 2, 0x100000]),
+            chunk(b"\0\0\0\0", &[0, deflated_size, 1]),
+            chunk(b"\0\0\0\0", &[deflated_size, 0x2000, 0]),
+        ]
+        .concat();
+
+        for (file, layout, algorithm) in [
+            (dark_souls, DcxLayout::Dcx, b"DFLT"),
+            (demons_souls, DcxLayout::Dcp, b"DFLT"),
+            (demons_souls_edge, DcxLayout::Dcp, b"EDGE"),
+        ] {
+            let (header, mut decoder) = DcxHeader::read(&file[..]).expect("invalid DCX header");
+            assert_eq!(header.layout(), layout);
+            assert_eq!(header.compression_parameters().algorithm(), algorithm);
+            assert_eq!(header.compression_parameters().level(), 9);
+
+            let mut decoded = Vec::new();
+            decoder
+                .read_to_end(&mut decoded)
+                .expect("failed to decode DCX");
+
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn skips_to_chunk_offsets() {
+        let data: Vec<u8> = (0..0x4000u32).map(|i| (i % 7) as u8).collect();
+        let compressed = compress(&data);
+
+        // Padding after the DCP and DCA chunks, with the offsets in the DCX chunk pointing past it.
+        let file = [
+            chunk(b"DCX\0", &[0x11000, 0x18, 0x24, 0x48, 0x58]),
+            chunk(b"DCS\0", &[data.len() as u32, compressed.len() as u32]),
+            chunk(
+                b"DCP\0",
+                &[
+                    u32::from_be_bytes(*b"DFLT"),
+                    0x20,
+                    0x9000000,
+                    0,
+                    0,
+                    0,
+                    0x10100,
+                ],
+            ),
+            vec![0xFF; 4],
+            chunk(b"DCA\0", &[8]),
+            vec![0xFF; 8],
+            compressed,
+        ]
+        .concat();
+
+        let (_, mut decoder) = DcxHeader::read(&file[..]).expect("invalid DCX header");
+        let mut decoded = Vec::new();
+        decoder
+            .read_to_end(&mut decoded)
+            .expect("failed to decode DCX");
+        assert_eq!(decoded, data);
+
+        let (_, decoded) = DcxHeader::decompress_to_vec(&file).expect("failed to decode DCX");
+        assert_eq!(decoded, data);
+
+        let (_, mut reader) =
+            DcxHeader::read_seekable(Cursor::new(&file)).expect("invalid DCX header");
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).expect("failed to read");
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn decompresses_and_seeks() {
+        let data: Vec<u8> = (0..0x2C000u32).map(|i| (i % 241) as u8).collect();
+
+        let mut algorithms = vec![
+            DcxAlgorithm::Deflate,
+            DcxAlgorithm::Zstd,
+            DcxAlgorithm::Edge,
+        ];
+        if Oodle::current().is_ok_and(|oodle| oodle.can_compress()) {
+            algorithms.push(DcxAlgorithm::Kraken);
+        }
+
+        for algorithm in algorithms {
+            let encoded = DcxEncoder::new(algorithm)
+                .encode_to_vec(&data)
+                .expect("failed to encode DCX");
+
+            let (_, decoded) =
+                DcxHeader::decompress_to_vec(&encoded).expect("failed to decode DCX");
+            assert_eq!(decoded, data);
+
+            let (_, mut reader) =
+                DcxHeader::read_seekable(Cursor::new(&encoded)).expect("invalid DCX header");
+
+            // Across the boundary of the second and third EDGE block, then back to the first.
+            for start in [0x1FFF0, 0x10] {
+                let mut buffer = [0u8; 0x20];
+                reader
+                    .seek(SeekFrom::Start(start as u64))
+                    .expect("failed to seek");
+                reader.read_exact(&mut buffer).expect("failed to read");
+
+                assert_eq!(buffer, data[start..start + 0x20]);
+            }
+
+            let mut tail = Vec::new();
+            reader.seek(SeekFrom::End(-0x100)).expect("failed to seek");
+            reader.read_to_end(&mut tail).expect("failed to read");
+            assert_eq!(tail, data[data.len() - 0x100..]);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let data: Vec<u8> = (0..0x12000u32).map(|i| (i % 241) as u8).collect();
+
+        for algorithm in [
+            DcxAlgorithm::Deflate,
+            DcxAlgorithm::Zstd,
+            DcxAlgorithm::Edge,
+        ] {
+            let encoded = DcxEncoder::new(algorithm)
+                .encode_to_vec(&data)
+                .expect("failed to encode DCX");
+
+            let Err(DcxError::Io(err)) =
+                DcxHeader::decompress_to_vec(&encoded[..encoded.len() - 1])
+            else {
+                panic!("decoded truncated {algorithm:?} data");
+            };
+            assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_edge_size() {
+        let data = vec![0x1u8; 0x12000];
+        let mut encoded = DcxEncoder::new(DcxAlgorithm::Edge)
+            .encode_to_vec(&data)
+            .expect("failed to encode DCX");
+
+        // Uncompressed size in the DCS chunk
+        encoded[0x1C..0x20].copy_from_slice(&(data.len() as u32 + 1).to_be_bytes());
+
+        let Err(DcxError::Io(err)) = DcxHeader::decompress_to_vec(&encoded) else {
+            panic!("decoded EDGE data with the wrong size");
+        };
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_wrong_uncompressed_sizes() {
+        let data = vec![0x1u8; 0x12000];
+
+        for algorithm in [DcxAlgorithm::Deflate, DcxAlgorithm::Zstd] {
+            let mut encoded = DcxEncoder::new(algorithm)
+                .encode_to_vec(&data)
+                .expect("failed to encode DCX");
+
+            for size in [data.len() as u32 - 1, data.len() as u32 + 1, u32::MAX] {
+                // Uncompressed size in the DCS chunk
+                encoded[0x1C..0x20].copy_from_slice(&size.to_be_bytes());
+
+                let Err(DcxError::UncompressedSizeMismatch { expected, actual }) =
+                    DcxHeader::decompress_to_vec(&encoded)
+                else {
+                    panic!("decoded {algorithm:?} data with the wrong size {size:#x}");
+                };
+                assert_eq!(expected, size as u64);
+                assert_eq!(actual, (data.len() as u64).min(size as u64 + 1));
+            }
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     io::{Error, Read, Result},
 };
 
-use fstools_oodle_rt::{decoder::OodleDecoder, Compressor, Oodle, OODLELZ_BLOCK_LEN};
+use fstools_oodle_rt::{decoder::OodleDecoder, Compressor, Oodle, OodleError, OODLELZ_BLOCK_LEN};
 
 // SAFETY: `OodleLZDecoder` pointer is safe to use across several threads.
 unsafe impl<R: Read + Sync> Sync for OodleReader<R> {}
@@ -41,7 +41,7 @@ pub struct OodleReader<R: Read> {
 }
 
 impl<R: Read> OodleReader<R> {
-    pub fn new(reader: R, uncompressed_size: u32) -> Option<Self> {
+    pub fn new(reader: R, uncompressed_size: u32) -> std::result::Result<Self, OodleError> {
         let oodle = Oodle::current()?;
         let decoder = oodle.create_decoder(
             Compressor::OodleLZ_Compressor_Kraken,
@@ -50,8 +50,7 @@ impl<R: Read> OodleReader<R> {
         let decode_buffer = vec![0u8; 3 * 1024 * 1024].into_boxed_slice();
         let io_buffer = vec![0u8; OODLELZ_BLOCK_LEN as usize * 2].into_boxed_slice();
 
-        Some(Self {
-            // SAFETY: Pointer is validated to be non-null above.
+        Ok(Self {
             decoder,
             reader,
             decode_buffer,
@@ -1,7 +1,9 @@
 /// Extensions for Rust standard library IO traits.
 mod read;
 mod widestring;
+mod write;
 pub mod zerocopy;
 
 pub use read::*;
 pub use widestring::*;
+pub use write::*;
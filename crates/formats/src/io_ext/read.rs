@@ -1,11 +1,13 @@
 use std::io::{ErrorKind, Read};
 
 use byteorder::{ByteOrder, ReadBytesExt};
+use encoding_rs::SHIFT_JIS;
 
 pub trait ReadFormatsExt {
     fn read_bool(&mut self) -> std::io::Result<bool>;
     fn read_magic<const LENGTH: usize>(&mut self, expected: &[u8; LENGTH]) -> std::io::Result<()>;
     fn read_utf16<BO: ByteOrder>(&mut self) -> std::io::Result<String>;
+    fn read_shift_jis(&mut self) -> std::io::Result<String>;
 
     fn read_padding(&mut self, length: usize) -> std::io::Result<()>;
 }
@@ -52,6 +54,29 @@ impl<R: Read> ReadFormatsExt for R {
             .map_err(|e| std::io::Error::new(ErrorKind::InvalidInput, e.to_string()))
     }
 
+    fn read_shift_jis(&mut self) -> std::io::Result<String> {
+        let mut buffer = Vec::new();
+
+        loop {
+            let current = self.read_u8()?;
+            if current != 0x0 {
+                buffer.push(current);
+            } else {
+                break;
+            }
+        }
+
+        let (value, had_errors) = SHIFT_JIS.decode_without_bom_handling(&buffer);
+        if had_errors {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid Shift-JIS string",
+            ));
+        }
+
+        Ok(value.into_owned())
+    }
+
     #[cfg(not(feature = "strict-padding"))]
     fn read_padding(&mut self, length: usize) -> std::io::Result<()> {
         let mut taken = self.take(length as u64);
@@ -0,0 +1,39 @@
+use std::io::{Read, Write};
+
+use byteorder::{ByteOrder, WriteBytesExt};
+use encoding_rs::SHIFT_JIS;
+
+pub trait WriteFormatsExt {
+    fn write_utf16<BO: ByteOrder>(&mut self, value: &str) -> std::io::Result<()>;
+    fn write_shift_jis(&mut self, value: &str) -> std::io::Result<()>;
+
+    fn write_padding(&mut self, length: usize) -> std::io::Result<()>;
+}
+
+impl<W: Write> WriteFormatsExt for W {
+    fn write_utf16<BO: ByteOrder>(&mut self, value: &str) -> std::io::Result<()> {
+        for unit in value.encode_utf16() {
+            self.write_u16::<BO>(unit)?;
+        }
+
+        self.write_u16::<BO>(0x0)
+    }
+
+    fn write_shift_jis(&mut self, value: &str) -> std::io::Result<()> {
+        let (encoded, _, had_errors) = SHIFT_JIS.encode(value);
+        if had_errors {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{value} can't be encoded as Shift-JIS"),
+            ));
+        }
+
+        self.write_all(&encoded)?;
+        self.write_u8(0x0)
+    }
+
+    fn write_padding(&mut self, length: usize) -> std::io::Result<()> {
+        std::io::copy(&mut std::io::repeat(0).take(length as u64), self)?;
+        Ok(())
+    }
+}
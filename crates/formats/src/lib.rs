@@ -1,5 +1,7 @@
 pub mod bhd;
+pub mod bnd3;
 pub mod bnd4;
+pub mod bxf4;
 pub mod dcx;
 pub mod entryfilelist;
 pub mod flver;
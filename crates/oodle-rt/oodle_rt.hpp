@@ -9,5 +9,7 @@ using Function_OodleLZDecoder_Create = decltype(OodleLZDecoder_Create);
 using Function_OodleLZDecoder_Destroy = decltype(OodleLZDecoder_Destroy);
 using Function_OodleLZDecoder_DecodeSome = decltype(OodleLZDecoder_DecodeSome);
 using Function_OodleLZ_Decompress = decltype(OodleLZ_Decompress);
+using Function_OodleLZ_Compress = decltype(OodleLZ_Compress);
+using Function_OodleLZ_GetCompressedBufferSizeNeeded = decltype(OodleLZ_GetCompressedBufferSizeNeeded);
 
 #endif
\ No newline at end of file
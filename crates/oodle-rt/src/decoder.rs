@@ -69,9 +69,7 @@ impl Default for DecodeSome_Out {
 
 impl Drop for OodleDecoder {
     fn drop(&mut self) {
-        let api = Oodle::current().expect("must be initialized to get an OodleDecoder");
-
-        // Safety: guaranteed to be a valid decoder pointer.
-        unsafe { (api.oodle_lz_decoder_destroy.expect("missing symbol"))(self.ptr.as_mut()) }
+        // Safety: guaranteed to be a valid decoder pointer, created by the same library.
+        unsafe { (self.oodle.oodle_lz_decoder_destroy.expect("missing symbol"))(self.ptr.as_mut()) }
     }
 }
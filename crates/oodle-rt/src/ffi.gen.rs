@@ -1376,3 +1376,20 @@ pub type Function_OodleLZ_Decompress = ::std::option::Option<
         arg14: OodleLZ_Decode_ThreadPhase,
     ) -> OO_SINTa,
 >;
+pub type Function_OodleLZ_Compress = ::std::option::Option<
+    unsafe extern "C" fn(
+        arg1: OodleLZ_Compressor,
+        arg2: *const ::std::os::raw::c_void,
+        arg3: OO_SINTa,
+        arg4: *mut ::std::os::raw::c_void,
+        arg5: OodleLZ_CompressionLevel,
+        arg6: *const OodleLZ_CompressOptions,
+        arg7: *const ::std::os::raw::c_void,
+        arg8: *const ::std::os::raw::c_void,
+        arg9: *mut ::std::os::raw::c_void,
+        arg10: OO_SINTa,
+    ) -> OO_SINTa,
+>;
+pub type Function_OodleLZ_GetCompressedBufferSizeNeeded = ::std::option::Option<
+    unsafe extern "C" fn(arg1: OodleLZ_Compressor, arg2: OO_SINTa) -> OO_SINTa,
+>;
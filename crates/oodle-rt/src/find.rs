@@ -0,0 +1,167 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use steamlocate::SteamDir;
+use walkdir::WalkDir;
+
+/// Environment variable naming the Oodle library, or a directory containing it.
+pub const OODLE_PATH_ENV: &str = "OODLE_PATH";
+
+const ER_APP_ID: u32 = 1245620;
+const SDT_APP_ID: u32 = 249078;
+const ACV_APP_ID: u32 = 1888160;
+
+/// Deep enough to find the library in the `Game` directory of a Steam install.
+const DEFAULT_MAX_DEPTH: usize = 2;
+
+#[cfg(target_os = "macos")]
+const SHARED_LIBRARY_EXTENSION: &str = "dylib";
+
+#[cfg(windows)]
+const SHARED_LIBRARY_EXTENSION: &str = "dll";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const SHARED_LIBRARY_EXTENSION: &str = "so";
+
+/// Where [`Oodle::find_with`](crate::Oodle::find_with) looks for the Oodle library.
+///
+/// An explicit path set with [`FindOptions::with_path`] is used first, then the `OODLE_PATH`
+/// environment variable. Either may name the library itself or a directory containing it. Without
+/// either, the extra roots, the Steam installs of the supported games and the current directory
+/// are searched in that order, [`FindOptions::with_max_depth`] levels deep.
+#[derive(Clone, Debug)]
+pub struct FindOptions {
+    path: Option<PathBuf>,
+    use_env: bool,
+    roots: Vec<PathBuf>,
+    search_steam: bool,
+    search_current_dir: bool,
+    max_depth: usize,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        Self {
+            path: None,
+            use_env: true,
+            roots: Vec::new(),
+            search_steam: true,
+            search_current_dir: true,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+impl FindOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the library at `path`, or search the directory at `path`, instead of the default
+    /// locations.
+    pub fn with_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Whether the `OODLE_PATH` environment variable is used, enabled by default.
+    pub fn with_env(mut self, use_env: bool) -> Self {
+        self.use_env = use_env;
+        self
+    }
+
+    /// Search `root` before the Steam installs and the current directory.
+    pub fn with_root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Whether the Steam installs of the supported games are searched, enabled by default.
+    pub fn with_steam(mut self, search_steam: bool) -> Self {
+        self.search_steam = search_steam;
+        self
+    }
+
+    /// Whether the current directory is searched, enabled by default.
+    pub fn with_current_dir(mut self, search_current_dir: bool) -> Self {
+        self.search_current_dir = search_current_dir;
+        self
+    }
+
+    /// How deep to search below each directory, where 1 only looks at the files directly in it.
+    /// Defaults to 2.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The path set explicitly or through the environment, which is used without searching the
+    /// default locations.
+    pub(crate) fn configured_path(&self) -> Option<PathBuf> {
+        self.path.clone().or_else(|| {
+            self.use_env
+                .then(|| env::var_os(OODLE_PATH_ENV))
+                .flatten()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    /// The directories to search when no path is configured.
+    pub(crate) fn search_roots(&self) -> Vec<PathBuf> {
+        let mut roots = self.roots.clone();
+
+        if self.search_steam {
+            roots.extend(steam_app_dirs());
+        }
+
+        if self.search_current_dir {
+            roots.extend(env::current_dir().ok());
+        }
+
+        roots
+    }
+
+    /// The first Oodle library found in `root`.
+    pub(crate) fn search(&self, root: &Path) -> Option<PathBuf> {
+        WalkDir::new(root)
+            .max_depth(self.max_depth)
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(walkdir::DirEntry::into_path)
+            .find(|path| is_oodle_library(path))
+    }
+}
+
+fn steam_app_dirs() -> Vec<PathBuf> {
+    let potential_apps = [ER_APP_ID, SDT_APP_ID, ACV_APP_ID];
+
+    SteamDir::locate()
+        .into_iter()
+        .flat_map(|steam| {
+            potential_apps.into_iter().filter_map(move |appid| {
+                let (app, library) = steam.find_app(appid).ok().flatten()?;
+
+                Some(library.resolve_app_dir(&app))
+            })
+        })
+        .collect()
+}
+
+fn is_oodle_library(path: &Path) -> bool {
+    let (Some(file_name), Some(file_ext)) = (
+        path.file_stem().and_then(|stem| stem.to_str()),
+        path.extension().and_then(|ext| ext.to_str()),
+    ) else {
+        return false;
+    };
+
+    // Might be a versioned .so, e.g. liboo2corelinux64.so.9
+    let is_shared_lib =
+        file_name.ends_with(SHARED_LIBRARY_EXTENSION) || file_ext == SHARED_LIBRARY_EXTENSION;
+    let is_oo2core = file_name.contains("oo2core");
+
+    is_shared_lib && is_oo2core
+}
@@ -1,20 +1,22 @@
 use std::{
-    error::Error,
     ffi::OsStr,
-    ptr::{null_mut, NonNull},
+    path::{Path, PathBuf},
+    ptr::{null, null_mut, NonNull},
     sync::{Arc, OnceLock, RwLock},
 };
 
 use decoder::OodleDecoder;
 use ffi::{
     Function_OodleLZDecoder_Create, Function_OodleLZDecoder_DecodeSome,
-    Function_OodleLZDecoder_Destroy,
+    Function_OodleLZDecoder_Destroy, Function_OodleLZ_Compress, Function_OodleLZ_Decompress,
+    Function_OodleLZ_GetCompressedBufferSizeNeeded, OodleLZ_CheckCRC, OodleLZ_FuzzSafe,
 };
 pub use ffi::{
     OodleLZ_Compressor as Compressor, OodleLZ_DecodeSome_Out as DecodeSome_Out,
     OodleLZ_Decode_ThreadPhase as DecodeThreadPhase, OodleLZ_Verbosity as Verbosity,
 };
 use libloading::Library;
+use thiserror::Error;
 
 #[allow(warnings)]
 pub(crate) mod ffi {
@@ -22,10 +24,11 @@ pub(crate) mod ffi {
 }
 
 pub use ffi::OODLELZ_BLOCK_LEN;
-use steamlocate::SteamDir;
-use walkdir::WalkDir;
+
+pub use self::find::{FindOptions, OODLE_PATH_ENV};
 
 pub mod decoder;
+mod find;
 
 #[derive(Clone)]
 pub struct Oodle {
@@ -35,74 +38,161 @@ pub struct Oodle {
     pub(crate) oodle_lz_decoder_create: Function_OodleLZDecoder_Create,
     pub(crate) oodle_lz_decoder_destroy: Function_OodleLZDecoder_Destroy,
     pub(crate) oodle_lz_decoder_decode_some: Function_OodleLZDecoder_DecodeSome,
+    pub(crate) oodle_lz_decompress: Function_OodleLZ_Decompress,
+
+    /// Compression entry points, only exported by builds of Oodle that ship the compressors.
+    pub(crate) oodle_lz_compress: Function_OodleLZ_Compress,
+    pub(crate) oodle_lz_get_compressed_buffer_size_needed:
+        Function_OodleLZ_GetCompressedBufferSizeNeeded,
 }
 
-const ER_APP_ID: u32 = 1245620;
-const SDT_APP_ID: u32 = 249078;
-const ACV_APP_ID: u32 = 1888160;
+#[derive(Debug, Error)]
+pub enum OodleError {
+    #[error(
+        "Could not find an Oodle library in {}, set {OODLE_PATH_ENV} to its location",
+        display_paths(.0)
+    )]
+    NotFound(Vec<PathBuf>),
+
+    #[error("Could not load the Oodle library at {path}: {source}")]
+    LoadFailed {
+        path: PathBuf,
+        source: libloading::Error,
+    },
+
+    #[error("Oodle failed to create a decoder")]
+    DecoderCreationFailed,
+
+    #[error("The loaded Oodle library doesn't export {0}")]
+    MissingSymbol(&'static str),
+
+    #[error("Buffer of {0} bytes is too large for Oodle")]
+    BufferTooLarge(usize),
 
-#[cfg(target_os = "macos")]
-const SHARED_LIBRARY_EXTENSION: &str = "dylib";
+    #[error("Oodle failed to compress the data")]
+    CompressionFailed,
 
-#[cfg(windows)]
-const SHARED_LIBRARY_EXTENSION: &str = "dll";
+    #[error("Oodle failed to decompress the data into {0} bytes")]
+    DecompressionFailed(usize),
+}
+
+/// How much time the compressor spends on making the output smaller. Decompression speed is
+/// mostly unaffected. DCX files store the level as its raw value, e.g. 6 for
+/// [`CompressionLevel::Optimal2`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum CompressionLevel {
+    HyperFast4 = -4,
+    HyperFast3 = -3,
+    HyperFast2 = -2,
+    HyperFast1 = -1,
+    None = 0,
+    SuperFast = 1,
+    VeryFast = 2,
+    Fast = 3,
+    #[default]
+    Normal = 4,
+    Optimal1 = 5,
+    Optimal2 = 6,
+    Optimal3 = 7,
+    Optimal4 = 8,
+    Optimal5 = 9,
+}
+
+impl CompressionLevel {
+    /// The level with the raw value `level`, as used by the Oodle API.
+    pub fn from_raw(level: i32) -> Option<Self> {
+        Some(match level {
+            -4 => Self::HyperFast4,
+            -3 => Self::HyperFast3,
+            -2 => Self::HyperFast2,
+            -1 => Self::HyperFast1,
+            0 => Self::None,
+            1 => Self::SuperFast,
+            2 => Self::VeryFast,
+            3 => Self::Fast,
+            4 => Self::Normal,
+            5 => Self::Optimal1,
+            6 => Self::Optimal2,
+            7 => Self::Optimal3,
+            8 => Self::Optimal4,
+            9 => Self::Optimal5,
+            _ => return None,
+        })
+    }
 
-#[cfg(all(unix, not(target_os = "macos")))]
-const SHARED_LIBRARY_EXTENSION: &str = "so";
+    pub fn raw(self) -> i32 {
+        self as i32
+    }
+}
 
+/// The current library, if one was found or made current.
 fn oodle_lock() -> &'static RwLock<Option<Oodle>> {
     static CURRENT_OODLE: OnceLock<RwLock<Option<Oodle>>> = OnceLock::new();
-    CURRENT_OODLE.get_or_init(|| RwLock::new(Oodle::find()))
+    CURRENT_OODLE.get_or_init(|| RwLock::new(None))
 }
 
 impl Oodle {
-    pub fn find() -> Option<Self> {
-        let potential_apps = &[ER_APP_ID, SDT_APP_ID, ACV_APP_ID];
-        let steam_app_paths: Vec<_> = SteamDir::locate()
-            .into_iter()
-            .flat_map(|steam| {
-                potential_apps.iter().filter_map(move |appid| {
-                    let (app, library) = steam.find_app(*appid).ok().flatten()?;
-
-                    Some(library.resolve_app_dir(&app))
-                })
-            })
-            .collect();
+    /// Find and load the Oodle library using the default [`FindOptions`].
+    pub fn find() -> Result<Self, OodleError> {
+        Self::find_with(&FindOptions::default())
+    }
+
+    /// Find and load the Oodle library as configured by `options`.
+    pub fn find_with(options: &FindOptions) -> Result<Self, OodleError> {
+        let path = match options.configured_path() {
+            Some(path) if path.is_dir() => options
+                .search(&path)
+                .ok_or_else(|| OodleError::NotFound(vec![path]))?,
+            Some(path) => path,
+            None => {
+                let roots = options.search_roots();
 
-        let oodle_dll_path = steam_app_paths
-            .into_iter()
-            .chain(std::env::current_dir().ok())
-            .flat_map(|dir| {
-                WalkDir::new(dir).into_iter().filter_map(|entry| {
-                    let entry = entry.ok()?;
-                    let file_name = entry.path().file_stem()?.to_str()?;
-                    let file_ext = entry.path().extension()?.to_str()?;
-
-                    // Might be a versioned .so, e.g. liboo2corelinux64.so.9
-                    let is_shared_lib = file_name.ends_with(SHARED_LIBRARY_EXTENSION)
-                        || file_ext == SHARED_LIBRARY_EXTENSION;
-                    let is_oo2core = file_name.contains("oo2core");
-
-                    if is_shared_lib && is_oo2core {
-                        Some(entry.into_path())
-                    } else {
-                        None
-                    }
-                })
-            })
-            .next()?;
-
-        // Safety: not at all
-        unsafe { Self::load(oodle_dll_path).ok() }
-    }
-
-    pub fn current() -> Option<Self> {
+                roots
+                    .iter()
+                    .find_map(|root| options.search(root))
+                    .ok_or(OodleError::NotFound(roots))?
+            }
+        };
+
+        // SAFETY: The library is either the one configured by the user or named like Oodle.
+        unsafe { Self::load(path) }
+    }
+
+    /// The library used by decoders, found with the default [`FindOptions`] unless another one was
+    /// made current before. Only a library that was found is kept, a failed search is repeated on
+    /// the next call.
+    pub fn current() -> Result<Self, OodleError> {
+        Self::current_with(Self::find)
+    }
+
+    /// [`Oodle::current`], calling `find` if no library is current yet.
+    fn current_with(find: impl FnOnce() -> Result<Self, OodleError>) -> Result<Self, OodleError> {
         let guard = match oodle_lock().read() {
             Ok(guard) => guard,
             Err(e) => e.into_inner(),
         };
 
-        guard.clone()
+        if let Some(oodle) = guard.as_ref() {
+            return Ok(oodle.clone());
+        }
+
+        drop(guard);
+
+        let mut guard = match oodle_lock().write() {
+            Ok(guard) => guard,
+            Err(e) => e.into_inner(),
+        };
+
+        // Another thread may have found or made one current while the lock was released.
+        if let Some(oodle) = guard.as_ref() {
+            return Ok(oodle.clone());
+        }
+
+        let oodle = find()?;
+        *guard = Some(oodle.clone());
+
+        Ok(oodle)
     }
 
     pub fn make_current(&self) {
@@ -121,17 +211,46 @@ impl Oodle {
     /// It is up to the caller to ensure that a correct Oodle library is loaded,
     /// and that no platform specific globals will be modified by the called initialization
     /// routines.
-    pub unsafe fn load<S: AsRef<OsStr>>(library: S) -> Result<Self, Box<dyn Error>> {
-        let library = Arc::new(libloading::Library::new(library)?);
-        let oodle_lz_decoder_create = *library.get(b"OodleLZDecoder_Create\0")?;
-        let oodle_lz_decoder_destroy = *library.get(b"OodleLZDecoder_Destroy\0")?;
-        let oodle_lz_decoder_decode_some = *library.get(b"OodleLZDecoder_DecodeSome\0")?;
+    pub unsafe fn load<S: AsRef<OsStr>>(library: S) -> Result<Self, OodleError> {
+        let path = Path::new(library.as_ref());
+        let library =
+            Arc::new(
+                libloading::Library::new(path).map_err(|source| OodleError::LoadFailed {
+                    path: path.to_path_buf(),
+                    source,
+                })?,
+            );
+
+        let symbol = |name: &'static str| OodleError::MissingSymbol(name);
+        let oodle_lz_decoder_create = *library
+            .get(b"OodleLZDecoder_Create\0")
+            .map_err(|_| symbol("OodleLZDecoder_Create"))?;
+        let oodle_lz_decoder_destroy = *library
+            .get(b"OodleLZDecoder_Destroy\0")
+            .map_err(|_| symbol("OodleLZDecoder_Destroy"))?;
+        let oodle_lz_decoder_decode_some = *library
+            .get(b"OodleLZDecoder_DecodeSome\0")
+            .map_err(|_| symbol("OodleLZDecoder_DecodeSome"))?;
+        let oodle_lz_decompress = *library
+            .get(b"OodleLZ_Decompress\0")
+            .map_err(|_| symbol("OodleLZ_Decompress"))?;
+        let oodle_lz_compress = library
+            .get::<Function_OodleLZ_Compress>(b"OodleLZ_Compress\0")
+            .map_or(None, |f| *f);
+        let oodle_lz_get_compressed_buffer_size_needed = library
+            .get::<Function_OodleLZ_GetCompressedBufferSizeNeeded>(
+                b"OodleLZ_GetCompressedBufferSizeNeeded\0",
+            )
+            .map_or(None, |f| *f);
 
         Ok(Oodle {
             library,
             oodle_lz_decoder_create,
             oodle_lz_decoder_destroy,
             oodle_lz_decoder_decode_some,
+            oodle_lz_decompress,
+            oodle_lz_compress,
+            oodle_lz_get_compressed_buffer_size_needed,
         })
     }
 
@@ -139,19 +258,217 @@ impl Oodle {
         &self,
         compressor: Compressor,
         uncompressed_size: usize,
-    ) -> Option<OodleDecoder> {
+    ) -> Result<OodleDecoder, OodleError> {
+        let decoder_create = self
+            .oodle_lz_decoder_create
+            .ok_or(OodleError::MissingSymbol("OodleLZDecoder_Create"))?;
+
         // Safety: all non-optional parameters passed are valid representations of their types
-        let ptr = unsafe {
-            (self.oodle_lz_decoder_create.expect("null fn ptr"))(
+        let ptr = unsafe { decoder_create(compressor, uncompressed_size as i64, null_mut(), 0) };
+
+        let ptr = NonNull::new(ptr).ok_or(OodleError::DecoderCreationFailed)?;
+
+        Ok(OodleDecoder::new(self.clone(), ptr, uncompressed_size))
+    }
+
+    /// Decompress `input` in a single call into a buffer of `uncompressed_size` bytes, which has to
+    /// be known up front. Fails if the data doesn't decompress to exactly that size.
+    pub fn decompress(
+        &self,
+        input: &[u8],
+        uncompressed_size: usize,
+    ) -> Result<Vec<u8>, OodleError> {
+        let decompress = self
+            .oodle_lz_decompress
+            .ok_or(OodleError::MissingSymbol("OodleLZ_Decompress"))?;
+        let input_len = buffer_len(input.len())?;
+        let output_len = buffer_len(uncompressed_size)?;
+        let mut output = vec![0u8; uncompressed_size];
+
+        // SAFETY: Both buffers are valid for the lengths passed, all optional parameters are null.
+        let written = unsafe {
+            decompress(
+                input.as_ptr().cast(),
+                input_len,
+                output.as_mut_ptr().cast(),
+                output_len,
+                OodleLZ_FuzzSafe::OodleLZ_FuzzSafe_Yes,
+                OodleLZ_CheckCRC::OodleLZ_CheckCRC_Yes,
+                Verbosity::OodleLZ_Verbosity_None,
+                null_mut(),
+                0,
+                None,
+                null_mut(),
+                null_mut(),
+                0,
+                DecodeThreadPhase::OodleLZ_Decode_Unthreaded,
+            )
+        };
+
+        if written != output_len {
+            return Err(OodleError::DecompressionFailed(uncompressed_size));
+        }
+
+        Ok(output)
+    }
+
+    /// Check if the loaded library exports the functions needed by [`Oodle::compress`].
+    pub fn can_compress(&self) -> bool {
+        self.oodle_lz_compress.is_some()
+            && self.oodle_lz_get_compressed_buffer_size_needed.is_some()
+    }
+
+    /// Compress `input` in a single call with `compressor`, e.g. Kraken for a balance of ratio and
+    /// speed, Leviathan for smaller output or Mermaid for faster decompression. Fails if the loaded
+    /// library can't compress, see [`Oodle::can_compress`].
+    pub fn compress(
+        &self,
+        compressor: Compressor,
+        level: CompressionLevel,
+        input: &[u8],
+    ) -> Result<Vec<u8>, OodleError> {
+        let compress = self
+            .oodle_lz_compress
+            .ok_or(OodleError::MissingSymbol("OodleLZ_Compress"))?;
+        let buffer_size_needed =
+            self.oodle_lz_get_compressed_buffer_size_needed
+                .ok_or(OodleError::MissingSymbol(
+                    "OodleLZ_GetCompressedBufferSizeNeeded",
+                ))?;
+        let input_len = buffer_len(input.len())?;
+
+        // SAFETY: Only takes plain values.
+        let output_len = unsafe { buffer_size_needed(compressor, input_len) };
+        // A negative size means Oodle can't compress this input with `compressor`.
+        let output_len = usize::try_from(output_len).map_err(|_| OodleError::CompressionFailed)?;
+        let mut output = vec![0u8; output_len];
+
+        // SAFETY: The output buffer is as large as Oodle requires for the input length, all
+        // optional parameters are null.
+        let written = unsafe {
+            compress(
                 compressor,
-                uncompressed_size as i64,
+                input.as_ptr().cast(),
+                input_len,
+                output.as_mut_ptr().cast(),
+                level.raw(),
+                null(),
+                null(),
+                null(),
                 null_mut(),
                 0,
             )
         };
 
-        let ptr = NonNull::new(ptr)?;
+        if written <= 0 {
+            return Err(OodleError::CompressionFailed);
+        }
+
+        output.truncate(written as usize);
+
+        Ok(output)
+    }
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    if paths.is_empty() {
+        return "no directories".to_string();
+    }
+
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn buffer_len(len: usize) -> Result<isize, OodleError> {
+    isize::try_from(len).map_err(|_| OodleError::BufferTooLarge(len))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::{CompressionLevel, Compressor, FindOptions, Oodle, OodleError};
+
+    const FORMATS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../formats");
+
+    #[test]
+    fn round_trips() {
+        let path = format!("{FORMATS_DIR}/liboo2corelinux64.so.9");
+
+        // SAFETY: The library checked into the repository is a release build of Oodle.
+        let oodle = unsafe { Oodle::load(path) }.expect("failed to load Oodle");
+        assert!(oodle.can_compress());
+
+        let data: Vec<u8> = (0..0x50000u32)
+            .map(|i| ((i % 251) ^ (i / 7)) as u8)
+            .collect();
+
+        for compressor in [
+            Compressor::OodleLZ_Compressor_Kraken,
+            Compressor::OodleLZ_Compressor_Leviathan,
+            Compressor::OodleLZ_Compressor_Mermaid,
+        ] {
+            let compressed = oodle
+                .compress(compressor, CompressionLevel::Optimal2, &data)
+                .expect("failed to compress");
+            assert!(compressed.len() < data.len());
+
+            let decompressed = oodle
+                .decompress(&compressed, data.len())
+                .expect("failed to decompress");
+            assert_eq!(decompressed, data);
+
+            let truncated = &compressed[..compressed.len() / 2];
+            assert!(oodle.decompress(truncated, data.len()).is_err());
+        }
+    }
+
+    #[test]
+    fn finds_configured_library() {
+        let only = |options: FindOptions| {
+            options
+                .with_env(false)
+                .with_steam(false)
+                .with_current_dir(false)
+        };
+
+        Oodle::find_with(&only(
+            FindOptions::new().with_root(FORMATS_DIR).with_max_depth(1),
+        ))
+        .expect("library in root wasn't found");
+        Oodle::find_with(&only(FindOptions::new().with_path(FORMATS_DIR)))
+            .expect("library in configured directory wasn't found");
+
+        let empty_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+        match Oodle::find_with(&only(FindOptions::new().with_root(empty_dir))) {
+            Err(err @ OodleError::NotFound(_)) => {
+                assert!(err.to_string().contains(empty_dir));
+                assert!(err.to_string().contains("OODLE_PATH"));
+            }
+            Err(err) => panic!("unexpected error: {err}"),
+            Ok(_) => panic!("library found in a directory without one"),
+        }
+    }
+
+    #[test]
+    fn retries_after_failed_lookup() {
+        let mut lookups = 0;
+        for _ in 0..2 {
+            let result = Oodle::current_with(|| {
+                lookups += 1;
+                Err(OodleError::NotFound(Vec::new()))
+            });
+            assert!(result.is_err());
+        }
+        assert_eq!(lookups, 2, "failed lookup was cached");
+
+        // SAFETY: The library checked into the repository is a release build of Oodle.
+        let oodle = unsafe { Oodle::load(format!("{FORMATS_DIR}/liboo2corelinux64.so.9")) }
+            .expect("failed to load Oodle");
+        oodle.make_current();
 
-        Some(OodleDecoder::new(self.clone(), ptr, uncompressed_size))
+        Oodle::current_with(|| panic!("library made current after a failed lookup wasn't used"))
+            .expect("library made current wasn't returned");
     }
 }
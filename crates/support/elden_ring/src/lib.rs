@@ -9,35 +9,38 @@ use fstools::{
     formats::{bnd4::BND4, dcx::DcxHeader},
 };
 
+pub use self::profile::{
+    Game, GameArchive, GameProfile, Regulation, ARMORED_CORE_6, DARK_SOULS_3, ELDEN_RING, SEKIRO,
+};
+
+mod profile;
+
+const ELDEN_RING_REGULATION_KEY: &[u8; 32] = &[
+    0x99, 0xBF, 0xFC, 0x36, 0x6A, 0x6B, 0xC8, 0xC6, 0xF5, 0x82, 0x7D, 0x09, 0x36, 0x02, 0xD6, 0x76,
+    0xC4, 0x28, 0x92, 0xA0, 0x1C, 0x20, 0x7F, 0xB0, 0x24, 0xD3, 0xAF, 0x4E, 0x49, 0x3F, 0xEF, 0x99,
+];
+
+/// Open the Elden Ring archives in `game_path`, see [`GameProfile::load_dvd_bnd`].
 pub fn load_dvd_bnd(
     game_path: PathBuf,
     keys: impl ArchiveKeyProvider,
 ) -> Result<DvdBnd, io::Error> {
-    let archives = [
-        game_path.join("Data0"),
-        game_path.join("Data1"),
-        game_path.join("Data2"),
-        game_path.join("Data3"),
-        game_path.join("sd/sd"),
-    ];
-
-    DvdBnd::create(archives, &keys)
+    ELDEN_RING.load_dvd_bnd(game_path, &keys)
 }
 
+/// The paths in the Elden Ring dictionary.
 pub fn dictionary() -> impl Iterator<Item = PathBuf> {
-    include_str!("data/EldenRingDictionary.txt")
-        .lines()
-        .filter(|l| !l.is_empty() && !l.starts_with('#'))
-        .map(std::path::PathBuf::from)
+    ELDEN_RING.dictionary()
 }
 
+/// Decrypt the Elden Ring regulation, see [`decrypt_regulation_with_key`].
 pub fn decrypt_regulation(reader: &mut impl Read) -> io::Result<Vec<u8>> {
-    const REGULATION_KEY: &[u8; 32] = &[
-        0x99, 0xBF, 0xFC, 0x36, 0x6A, 0x6B, 0xC8, 0xC6, 0xF5, 0x82, 0x7D, 0x09, 0x36, 0x02, 0xD6,
-        0x76, 0xC4, 0x28, 0x92, 0xA0, 0x1C, 0x20, 0x7F, 0xB0, 0x24, 0xD3, 0xAF, 0x4E, 0x49, 0x3F,
-        0xEF, 0x99,
-    ];
+    decrypt_regulation_with_key(reader, ELDEN_RING_REGULATION_KEY)
+}
 
+/// Decrypt a regulation file, which is AES-256-CBC encrypted with its IV stored in front of the
+/// data.
+pub fn decrypt_regulation_with_key(reader: &mut impl Read, key: &[u8; 32]) -> io::Result<Vec<u8>> {
     let mut iv = [0u8; 16];
     reader.read_exact(&mut iv)?;
 
@@ -45,7 +48,7 @@ pub fn decrypt_regulation(reader: &mut impl Read) -> io::Result<Vec<u8>> {
     reader.read_to_end(&mut out_buf)?;
 
     type Aes256Cbc = cbc::Decryptor<aes::Aes256>;
-    let mut cipher = Aes256Cbc::new_from_slices(REGULATION_KEY, &iv).unwrap();
+    let mut cipher = Aes256Cbc::new_from_slices(key, &iv).unwrap();
 
     // SAFETY: GenericArray<u8, _> is safe to transmute from an equiv. slice of u8s
     unsafe {
@@ -55,15 +58,16 @@ pub fn decrypt_regulation(reader: &mut impl Read) -> io::Result<Vec<u8>> {
     Ok(out_buf)
 }
 
+/// Read the Elden Ring regulation, see [`GameProfile::load_regulation`].
 pub fn load_regulation(game_path: impl AsRef<Path>) -> io::Result<BND4> {
-    let regulation_bytes = std::fs::read(game_path.as_ref().join("regulation.bin"))?;
-    let dcx_bytes = decrypt_regulation(&mut regulation_bytes.as_slice())?;
+    ELDEN_RING.load_regulation(game_path)
+}
 
-    let (_, mut dcx_decoder) = DcxHeader::read(io::Cursor::new(dcx_bytes))
-        .map_err(|_| io::Error::other("DCX header reading failed"))?;
+fn read_regulation(regulation_bytes: &[u8], key: &[u8; 32]) -> io::Result<BND4> {
+    let dcx_bytes = decrypt_regulation_with_key(&mut &regulation_bytes[..], key)?;
 
-    let mut bnd4_bytes = Vec::new();
-    dcx_decoder.read_to_end(&mut bnd4_bytes)?;
+    let (_, bnd4_bytes) = DcxHeader::decompress_to_vec(&dcx_bytes)
+        .map_err(|_| io::Error::other("DCX decompression failed"))?;
 
     BND4::from_reader(io::Cursor::new(bnd4_bytes))
         .map_err(|_| io::Error::other("Failed to read regulation BND4"))
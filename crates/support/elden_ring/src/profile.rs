@@ -0,0 +1,320 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use fstools::{
+    dvdbnd::{ArchiveKeyProvider, DvdBnd, HashAlgorithm, TocCache},
+    formats::bnd4::BND4,
+};
+
+use crate::{read_regulation, ELDEN_RING_REGULATION_KEY};
+
+/// The games with known archive layouts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Game {
+    EldenRing,
+    Sekiro,
+    DarkSouls3,
+    ArmoredCore6,
+}
+
+impl Game {
+    pub const ALL: [Game; 4] = [
+        Game::EldenRing,
+        Game::Sekiro,
+        Game::DarkSouls3,
+        Game::ArmoredCore6,
+    ];
+
+    pub fn profile(self) -> &'static GameProfile {
+        match self {
+            Game::EldenRing => &ELDEN_RING,
+            Game::Sekiro => &SEKIRO,
+            Game::DarkSouls3 => &DARK_SOULS_3,
+            Game::ArmoredCore6 => &ARMORED_CORE_6,
+        }
+    }
+
+    /// Guess which game is installed in `game_path`, first by looking for its executable and
+    /// then by the archives in the directory. Armored Core VI can only be told apart from Elden
+    /// Ring by its executable.
+    pub fn detect<P: AsRef<Path>>(game_path: P) -> Option<Game> {
+        let game_path = game_path.as_ref();
+        let exists = |file: &str| game_path.join(file).exists();
+
+        if let Some(game) = Self::ALL
+            .into_iter()
+            .find(|game| exists(game.profile().executable))
+        {
+            return Some(game);
+        }
+
+        if exists("Data0.bhd") && exists("sd/sd.bhd") {
+            Some(Game::EldenRing)
+        } else if exists("Data1.bhd") && exists("Data0.bdt") {
+            Some(Game::DarkSouls3)
+        } else if exists("Data1.bhd") {
+            Some(Game::Sekiro)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.profile().id)
+    }
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.to_ascii_lowercase();
+
+        Self::ALL
+            .into_iter()
+            .find(|game| game.profile().id == value || game.profile().aliases.contains(&&*value))
+            .ok_or_else(|| {
+                let ids: Vec<_> = Self::ALL.iter().map(|game| game.profile().id).collect();
+
+                format!("unknown game {value}, expected one of {}", ids.join(", "))
+            })
+    }
+}
+
+/// A BHD/BDT archive pair of a game.
+#[derive(Debug)]
+pub struct GameArchive {
+    /// Path of the archive relative to the game directory, without extension.
+    pub path: &'static str,
+
+    /// Name of the key the BHD is encrypted with, as passed to [`ArchiveKeyProvider::get_key`].
+    pub key: &'static str,
+
+    /// Whether the archive may be missing, e.g. because it belongs to a DLC.
+    pub optional: bool,
+}
+
+/// The encrypted regulation file of a game, which holds its params.
+#[derive(Debug)]
+pub struct Regulation {
+    /// Path of the file relative to the game directory.
+    pub path: &'static str,
+
+    /// AES-256 key the file is encrypted with, using CBC with the IV stored in front of the data.
+    pub key: &'static [u8; 32],
+}
+
+/// Everything that differs between games when opening their archives.
+#[derive(Debug)]
+pub struct GameProfile {
+    pub game: Game,
+
+    /// Short name of the game, as accepted by `--game`.
+    pub id: &'static str,
+
+    /// Other names accepted for the game.
+    pub aliases: &'static [&'static str],
+
+    /// The executable, used to recognize the game directory.
+    pub executable: &'static str,
+    pub archives: &'static [GameArchive],
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Known archive paths, one per line. Only included for games we maintain a dictionary for.
+    pub dictionary: Option<&'static str>,
+    pub regulation: Option<Regulation>,
+}
+
+impl GameProfile {
+    /// Paths of the archives in `game_path`, leaving out optional archives that aren't installed.
+    pub fn archive_paths<P: AsRef<Path>>(&self, game_path: P) -> Vec<(PathBuf, &'static str)> {
+        let game_path = game_path.as_ref();
+
+        self.archives
+            .iter()
+            .map(|archive| (game_path.join(archive.path), archive))
+            .filter(|(path, archive)| !archive.optional || path.with_extension("bhd").exists())
+            .map(|(path, archive)| (path, archive.key))
+            .collect()
+    }
+
+    /// Open the archives of the game installed in `game_path`, with the dictionary of the game
+    /// attached if there is one.
+    pub fn load_dvd_bnd<P: AsRef<Path>>(
+        &self,
+        game_path: P,
+        keys: &impl ArchiveKeyProvider,
+    ) -> io::Result<DvdBnd> {
+        let dvd_bnd =
+            DvdBnd::create_with_keys(self.archive_paths(game_path), keys, self.hash_algorithm)?;
+
+        Ok(dvd_bnd.with_dictionary(self.dictionary()))
+    }
+
+    /// Like [`GameProfile::load_dvd_bnd`], but with the BHD entries stored in `cache` to speed
+    /// up later loads.
+    pub fn load_dvd_bnd_cached<P: AsRef<Path>>(
+        &self,
+        game_path: P,
+        keys: &impl ArchiveKeyProvider,
+        cache: &TocCache,
+    ) -> io::Result<DvdBnd> {
+        let dvd_bnd = DvdBnd::create_with_cache(
+            self.archive_paths(game_path),
+            keys,
+            self.hash_algorithm,
+            cache,
+        )?;
+
+        Ok(dvd_bnd.with_dictionary(self.dictionary()))
+    }
+
+    /// The paths in the dictionary of this game, which is empty if there is none.
+    pub fn dictionary(&self) -> impl Iterator<Item = PathBuf> {
+        self.dictionary
+            .unwrap_or_default()
+            .lines()
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(PathBuf::from)
+    }
+
+    /// Decrypt and read the regulation of the game installed in `game_path`.
+    pub fn load_regulation<P: AsRef<Path>>(&self, game_path: P) -> io::Result<BND4> {
+        let regulation = self.regulation.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} has no known regulation", self.id),
+            )
+        })?;
+
+        let regulation_bytes = std::fs::read(game_path.as_ref().join(regulation.path))?;
+
+        read_regulation(&regulation_bytes, regulation.key)
+    }
+}
+
+const ELDEN_RING_ARCHIVES: &[GameArchive] = &[
+    required("Data0"),
+    required("Data1"),
+    required("Data2"),
+    required("Data3"),
+    GameArchive {
+        path: "sd/sd",
+        key: "sd",
+        optional: false,
+    },
+];
+
+pub static ELDEN_RING: GameProfile = GameProfile {
+    game: Game::EldenRing,
+    id: "er",
+    aliases: &["eldenring", "elden-ring"],
+    executable: "eldenring.exe",
+    archives: ELDEN_RING_ARCHIVES,
+    hash_algorithm: HashAlgorithm::Hash64,
+    dictionary: Some(include_str!("data/EldenRingDictionary.txt")),
+    regulation: Some(Regulation {
+        path: "regulation.bin",
+        key: ELDEN_RING_REGULATION_KEY,
+    }),
+};
+
+/// Sekiro keeps its params in the archives rather than in a separate regulation file.
+pub static SEKIRO: GameProfile = GameProfile {
+    game: Game::Sekiro,
+    id: "sekiro",
+    aliases: &["sdt"],
+    executable: "sekiro.exe",
+    archives: &[
+        required("Data1"),
+        required("Data2"),
+        required("Data3"),
+        required("Data4"),
+        required("Data5"),
+    ],
+    hash_algorithm: HashAlgorithm::Hash32,
+    dictionary: None,
+    regulation: None,
+};
+
+pub static DARK_SOULS_3: GameProfile = GameProfile {
+    game: Game::DarkSouls3,
+    id: "ds3",
+    aliases: &["darksouls3", "dark-souls-3"],
+    executable: "DarkSoulsIII.exe",
+    archives: &[
+        required("Data1"),
+        required("Data2"),
+        required("Data3"),
+        required("Data4"),
+        required("Data5"),
+        GameArchive {
+            path: "DLC1",
+            key: "DLC1",
+            optional: true,
+        },
+        GameArchive {
+            path: "DLC2",
+            key: "DLC2",
+            optional: true,
+        },
+    ],
+    hash_algorithm: HashAlgorithm::Hash32,
+    dictionary: None,
+    regulation: Some(Regulation {
+        path: "Data0.bdt",
+        key: b"ds3#jn/8_7(rsY9pg55GFN7VFL#+3n/)",
+    }),
+};
+
+/// The regulation key of Armored Core VI isn't included, so its regulation can't be read yet.
+pub static ARMORED_CORE_6: GameProfile = GameProfile {
+    game: Game::ArmoredCore6,
+    id: "ac6",
+    aliases: &["armoredcore6", "armored-core-6"],
+    executable: "armoredcore6.exe",
+    archives: ELDEN_RING_ARCHIVES,
+    hash_algorithm: HashAlgorithm::Hash64,
+    dictionary: None,
+    regulation: None,
+};
+
+const fn required(path: &'static str) -> GameArchive {
+    GameArchive {
+        path,
+        key: path,
+        optional: false,
+    }
+}
+
+#[cfg(all(test, feature = "embedded-keys"))]
+mod test {
+    use fstools::dvdbnd::{ArchiveKeyProvider, EmbeddedKeyProvider};
+
+    use super::Game;
+
+    /// Needs the key of every archive of every game in `keys/<game>/`, see
+    /// [`EmbeddedKeyProvider`].
+    #[test]
+    fn embeds_archive_keys() {
+        for game in Game::ALL {
+            let profile = game.profile();
+            let keys = EmbeddedKeyProvider::new(profile.id);
+
+            for archive in profile.archives {
+                if let Err(err) = keys.get_key(archive.key) {
+                    panic!(
+                        "no usable {} key is embedded for {game}: {err}",
+                        archive.key
+                    );
+                }
+            }
+        }
+    }
+}
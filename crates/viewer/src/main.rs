@@ -8,7 +8,10 @@ use fstools_asset_server::{
     types::{bnd4::Archive, flver::FlverAsset},
     FsAssetSourcePlugin, FsFormatsPlugin,
 };
+use fstools_config::Options;
 use fstools_dvdbnd::FileKeyProvider;
+use fstools_elden_ring_support::Game;
+use fstools_oodle_rt::{FindOptions, Oodle};
 
 use crate::{
     formats::FormatsPlugins,
@@ -22,16 +25,22 @@ fn main() {
     let args = Args::parse();
     let er_path = args.erpath.expect("no path to Elden Ring game provided");
 
+    let config = Options::load().unwrap_or_default();
+    if let Some(oodle) = &config.paths.oodle {
+        Oodle::find_with(&FindOptions::new().with_path(oodle))
+            .expect("configured Oodle library couldn't be loaded")
+            .make_current();
+    }
+
     let keys = FileKeyProvider::new("keys");
-    let archives = [
-        er_path.join("Data0"),
-        er_path.join("Data1"),
-        er_path.join("Data2"),
-        er_path.join("Data3"),
-        er_path.join("sd/sd"),
-    ];
+    let game = Game::detect(&er_path).unwrap_or(Game::EldenRing);
+    let dvd_bnd = game
+        .profile()
+        .load_dvd_bnd(&er_path, &keys)
+        .expect("assets_failure");
+
     App::new()
-        .add_plugins(FsAssetSourcePlugin::new(&archives, keys).expect("assets_failure"))
+        .add_plugins(FsAssetSourcePlugin::from_dvd_bnd(dvd_bnd))
         .add_plugins(DefaultPlugins.set(AssetPlugin {
             watch_for_changes_override: Some(true),
             ..Default::default()
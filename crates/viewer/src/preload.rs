@@ -33,7 +33,7 @@ pub fn vfs_mount_system(
             .map(|(name, handle)| {
                 (
                     name.clone(),
-                    archive_entries.remove(handle).expect("no data").data,
+                    archive_entries.remove(handle).expect("no data"),
                 )
             })
             .collect();
@@ -43,8 +43,8 @@ pub fn vfs_mount_system(
 
         io_pool
             .spawn(async move {
-                for (name, data) in entries {
-                    vfs.mount_file(name, data);
+                for (name, entry) in entries {
+                    vfs.mount_file(name, entry);
                 }
             })
             .detach();
@@ -0,0 +1,106 @@
+use std::{
+    error::Error,
+    ffi::OsStr,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use fstools::{
+    formats::{bnd4::BND4, dcx::DcxHeader},
+    prelude::*,
+};
+use fstools_elden_ring_support::{decrypt_regulation, dictionary};
+use libtest_mimic::{Arguments, Failed, Trial};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Arguments::from_args();
+    let er_path = PathBuf::from(std::env::var("ER_PATH").expect("er_path"));
+    let reg_path = er_path.join("regulation.bin");
+    let keys_path = PathBuf::from(std::env::var("ER_KEYS_PATH").expect("er_keys_path"));
+    let vfs = Arc::new(fstools_elden_ring_support::load_dvd_bnd(
+        er_path,
+        FileKeyProvider::new(keys_path),
+    )?);
+
+    let mut tests = vec![];
+
+    for line in dictionary().filter(|line| is_binder(line)) {
+        let vfs = vfs.clone();
+        let test = Trial::test(line.to_string_lossy().to_string(), move || {
+            check_file(vfs.clone(), &line)
+        })
+        .with_kind("bnd4");
+
+        tests.push(test);
+    }
+
+    // The regulation is a BND4 of params once decrypted
+    tests.push(Trial::test("regulation.bin", move || {
+        check_regulation(&reg_path)
+    }));
+
+    libtest_mimic::run(&args, tests).exit();
+}
+
+/// Whether `path` names a DCX compressed binder, e.g. `chr/c0000.chrbnd.dcx`.
+fn is_binder(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new("dcx"))
+        && Path::new(path.file_stem().unwrap_or_default())
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| extension.ends_with("bnd"))
+}
+
+pub fn check_regulation(path: &Path) -> Result<(), Failed> {
+    let regulation_bytes = std::fs::read(path)?;
+    let dcx_bytes = decrypt_regulation(&mut regulation_bytes.as_slice())?;
+    check_bnd4(io::Cursor::new(dcx_bytes))
+}
+
+pub fn check_file(vfs: Arc<DvdBnd>, file: &Path) -> Result<(), Failed> {
+    let file = match vfs.open(file.to_string_lossy().as_ref()) {
+        Ok(file) => file,
+        Err(_) => {
+            return Ok(());
+        }
+    };
+    check_bnd4(file)
+}
+
+/// Check that rewriting the BND4 in a DCX container produces the exact bytes it was read from.
+pub fn check_bnd4(reader: impl Read) -> Result<(), Failed> {
+    let (_, mut decoder) =
+        DcxHeader::read(reader).map_err(|err| format!("failed to parse DCX header: {err}"))?;
+
+    let mut bytes = Vec::new();
+    decoder.read_to_end(&mut bytes)?;
+
+    // Older binders in the archives are BND3, which has its own round trip tests
+    if !bytes.starts_with(b"BND4") {
+        return Ok(());
+    }
+
+    let bnd = BND4::from_reader(io::Cursor::new(&bytes))
+        .map_err(|err| format!("failed to parse BND4: {err}"))?;
+
+    let mut rewritten = Vec::with_capacity(bytes.len());
+    bnd.write(&mut rewritten)?;
+
+    if rewritten != bytes {
+        let position = rewritten
+            .iter()
+            .zip(&bytes)
+            .position(|(a, b)| a != b)
+            .unwrap_or(rewritten.len().min(bytes.len()));
+
+        return Err(format!(
+            "rewritten archive differs at {position:#x}, {:#x} bytes written for {:#x} read",
+            rewritten.len(),
+            bytes.len()
+        )
+        .into());
+    }
+
+    Ok(())
+}
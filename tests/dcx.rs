@@ -9,7 +9,6 @@ use std::{
 
 use fstools::{formats::dcx::DcxHeader, prelude::*};
 use fstools_elden_ring_support::{decrypt_regulation, dictionary};
-use fstools_formats::dcx::DcxError;
 use insta::assert_snapshot;
 use libtest_mimic::{Arguments, Failed, Trial};
 
@@ -50,7 +49,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 pub fn check_regulation(path: &Path) -> Result<(), Failed> {
-    let regulation_bytes = std::fs::read(path.join("regulation.bin"))?;
+    let regulation_bytes = std::fs::read(path)?;
     let dcx_bytes = decrypt_regulation(&mut regulation_bytes.as_slice())?;
     check_dcx(io::Cursor::new(dcx_bytes))
 }
@@ -66,11 +65,8 @@ pub fn check_file(vfs: Arc<DvdBnd>, file: &Path) -> Result<(), Failed> {
 }
 
 pub fn check_dcx(reader: impl Read) -> Result<(), Failed> {
-    let (_, mut decoder) = match DcxHeader::read(reader) {
-        Ok(details) => details,
-        Err(DcxError::UnknownAlgorithm(_)) => return Ok(()),
-        Err(_) => return Err("failed to parse DCX header".into()),
-    };
+    let (_, mut decoder) =
+        DcxHeader::read(reader).map_err(|err| format!("failed to parse DCX header: {err}"))?;
 
     std::io::copy(&mut decoder, &mut std::io::sink())?;
 